@@ -0,0 +1,253 @@
+use std::env;
+use std::net::{AddrParseError, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{Result, McpImageError};
+use crate::handlers::DEFAULT_PAGE_SIZE;
+use crate::transport::DEFAULT_SESSION_TIMEOUT;
+
+/// Environment variable overriding the number of concurrent conversions
+pub const MAX_CONVERSIONS_ENV: &str = "MCP_MAX_CONCURRENT_CONVERSIONS";
+
+/// Environment variable selecting the HTTP transport and its listen address
+/// (a bare port listens on 127.0.0.1)
+pub const HTTP_ADDR_ENV: &str = "MCP_HTTP_ADDR";
+
+/// Environment variable holding the bearer token HTTP clients must send
+pub const HTTP_TOKEN_ENV: &str = "MCP_HTTP_TOKEN";
+
+/// Environment variable selecting the Unix domain socket transport and its path
+pub const SOCKET_PATH_ENV: &str = "MCP_SOCKET_PATH";
+
+/// Environment variable listing browser origins allowed to reach the HTTP
+/// transport (comma separated)
+pub const HTTP_ALLOWED_ORIGINS_ENV: &str = "MCP_HTTP_ALLOWED_ORIGINS";
+
+/// Environment variable setting how long, in seconds, an unused HTTP session
+/// is kept (0 keeps sessions until the client deletes them)
+pub const HTTP_SESSION_TIMEOUT_ENV: &str = "MCP_HTTP_SESSION_TIMEOUT_SECS";
+
+/// Environment variable listing directories exposed as resources
+/// (separated like `PATH`)
+pub const RESOURCE_ROOTS_ENV: &str = "MCP_RESOURCE_ROOTS";
+
+/// Environment variable naming a directory of extra prompt templates
+pub const PROMPTS_DIR_ENV: &str = "MCP_PROMPTS_DIR";
+
+/// Environment variable setting how often, in seconds, converter binaries
+/// are looked up to detect tools appearing or disappearing (0 disables)
+pub const CONVERTER_POLL_ENV: &str = "MCP_CONVERTER_POLL_SECS";
+
+/// Environment variable setting how many tools a `tools/list` page holds
+pub const TOOLS_PAGE_SIZE_ENV: &str = "MCP_TOOLS_PAGE_SIZE";
+
+/// Default cap on converter processes running at the same time
+const DEFAULT_MAX_CONVERSIONS: usize = 4;
+
+/// Default interval between converter availability checks
+const DEFAULT_CONVERTER_POLL: Duration = Duration::from_secs(5);
+
+/// How the server talks to its clients
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportKind {
+    /// A single client over stdin/stdout (default)
+    Stdio,
+    /// Streamable HTTP, serving any number of sessions
+    Http(SocketAddr),
+    /// Newline-delimited JSON-RPC on a Unix domain socket, one session per
+    /// connection
+    Unix(PathBuf),
+}
+
+/// Runtime configuration for the server
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Maximum number of conversions allowed to run at the same time
+    pub max_concurrent_conversions: usize,
+    /// Transport selected at startup
+    pub transport: TransportKind,
+    /// `Origin` header values accepted by the HTTP transport. Requests
+    /// without an `Origin` header (non-browser clients) are always accepted.
+    pub http_allowed_origins: Vec<String>,
+    /// How long an HTTP session with no open stream and no running request
+    /// is kept; zero keeps sessions until DELETE
+    pub http_session_timeout: Duration,
+    /// Token the HTTP transport requires as `Authorization: Bearer`; any
+    /// client may connect when unset
+    pub http_bearer_token: Option<String>,
+    /// Directories whose image files are listed and readable as resources
+    pub resource_roots: Vec<PathBuf>,
+    /// Directory of `*.json` prompt templates served next to the built-ins
+    pub prompts_dir: Option<PathBuf>,
+    /// How often to check for converters being installed or removed, which
+    /// changes the tool list; zero disables the checks
+    pub converter_poll_interval: Duration,
+    /// Tools returned per `tools/list` page before a `nextCursor`
+    pub tools_page_size: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_conversions: DEFAULT_MAX_CONVERSIONS,
+            transport: TransportKind::Stdio,
+            http_allowed_origins: Vec::new(),
+            http_session_timeout: DEFAULT_SESSION_TIMEOUT,
+            http_bearer_token: None,
+            resource_roots: Vec::new(),
+            prompts_dir: None,
+            converter_poll_interval: DEFAULT_CONVERTER_POLL,
+            tools_page_size: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Build configuration from environment variables, falling back to defaults
+    pub fn from_env() -> Self {
+        let mut config = Self::default();
+        
+        if let Ok(value) = env::var(MAX_CONVERSIONS_ENV) {
+            match value.trim().parse::<usize>() {
+                Ok(n) if n > 0 => config.max_concurrent_conversions = n,
+                _ => eprintln!(
+                    "Ignoring invalid {}={:?}, using {}",
+                    MAX_CONVERSIONS_ENV, value, config.max_concurrent_conversions
+                ),
+            }
+        }
+        
+        if let Ok(value) = env::var(HTTP_ADDR_ENV) {
+            match parse_http_addr(&value) {
+                Ok(addr) => config.transport = TransportKind::Http(addr),
+                Err(e) => eprintln!("Ignoring invalid {}={:?}: {}", HTTP_ADDR_ENV, value, e),
+            }
+        }
+        
+        if let Ok(value) = env::var(SOCKET_PATH_ENV) {
+            if !value.trim().is_empty() {
+                config.transport = TransportKind::Unix(PathBuf::from(value.trim()));
+            }
+        }
+        
+        if let Ok(value) = env::var(HTTP_ALLOWED_ORIGINS_ENV) {
+            config.http_allowed_origins = value
+                .split(',')
+                .map(|origin| origin.trim().to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect();
+        }
+        
+        if let Ok(value) = env::var(HTTP_TOKEN_ENV) {
+            if !value.trim().is_empty() {
+                config.http_bearer_token = Some(value.trim().to_string());
+            }
+        }
+        
+        if let Ok(value) = env::var(HTTP_SESSION_TIMEOUT_ENV) {
+            match value.trim().parse::<u64>() {
+                Ok(secs) => config.http_session_timeout = Duration::from_secs(secs),
+                Err(e) => eprintln!("Ignoring invalid {}={:?}: {}", HTTP_SESSION_TIMEOUT_ENV, value, e),
+            }
+        }
+        
+        if let Some(value) = env::var_os(RESOURCE_ROOTS_ENV) {
+            config.resource_roots = env::split_paths(&value)
+                .filter(|path| !path.as_os_str().is_empty())
+                .collect();
+        }
+        
+        if let Ok(value) = env::var(PROMPTS_DIR_ENV) {
+            if !value.trim().is_empty() {
+                config.prompts_dir = Some(PathBuf::from(value.trim()));
+            }
+        }
+        
+        if let Ok(value) = env::var(CONVERTER_POLL_ENV) {
+            match value.trim().parse::<u64>() {
+                Ok(secs) => config.converter_poll_interval = Duration::from_secs(secs),
+                Err(e) => eprintln!("Ignoring invalid {}={:?}: {}", CONVERTER_POLL_ENV, value, e),
+            }
+        }
+        
+        if let Ok(value) = env::var(TOOLS_PAGE_SIZE_ENV) {
+            match value.trim().parse::<usize>() {
+                Ok(n) if n > 0 => config.tools_page_size = n,
+                _ => eprintln!(
+                    "Ignoring invalid {}={:?}, using {}",
+                    TOOLS_PAGE_SIZE_ENV, value, config.tools_page_size
+                ),
+            }
+        }
+        
+        config
+    }
+    
+    /// Build configuration from the environment, then apply command line
+    /// flags on top (flags win):
+    ///
+    /// - `--stdio` serve a single client over stdin/stdout
+    /// - `--http <addr>` serve streamable HTTP on `addr`, e.g. `127.0.0.1:8080`,
+    ///   or on 127.0.0.1 given just a port
+    /// - `--socket <path>` serve clients connecting to a Unix domain socket
+    /// - `--root <path>` expose a directory as resources (repeatable)
+    /// - `--prompts <dir>` load additional prompt templates from `dir`
+    pub fn from_env_and_args<I>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config = Self::from_env();
+        let mut args = args.into_iter();
+        
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--stdio" => config.transport = TransportKind::Stdio,
+                "--http" => {
+                    let value = args.next().ok_or_else(|| {
+                        McpImageError::InvalidInput("--http requires an address".to_string())
+                    })?;
+                    let addr = parse_http_addr(&value).map_err(|e| {
+                        McpImageError::InvalidInput(format!("Invalid --http address {:?}: {}", value, e))
+                    })?;
+                    config.transport = TransportKind::Http(addr);
+                }
+                "--socket" => {
+                    let value = args.next().ok_or_else(|| {
+                        McpImageError::InvalidInput("--socket requires a path".to_string())
+                    })?;
+                    config.transport = TransportKind::Unix(PathBuf::from(value));
+                }
+                "--root" => {
+                    let value = args.next().ok_or_else(|| {
+                        McpImageError::InvalidInput("--root requires a path".to_string())
+                    })?;
+                    config.resource_roots.push(PathBuf::from(value));
+                }
+                "--prompts" => {
+                    let value = args.next().ok_or_else(|| {
+                        McpImageError::InvalidInput("--prompts requires a directory".to_string())
+                    })?;
+                    config.prompts_dir = Some(PathBuf::from(value));
+                }
+                other => {
+                    return Err(McpImageError::InvalidInput(format!(
+                        "Unknown argument: {}",
+                        other
+                    )));
+                }
+            }
+        }
+        
+        Ok(config)
+    }
+}
+
+/// A listen address, or a bare port to listen on 127.0.0.1 only
+fn parse_http_addr(value: &str) -> std::result::Result<SocketAddr, AddrParseError> {
+    let value = value.trim();
+    match value.parse::<u16>() {
+        Ok(port) => Ok(SocketAddr::from((Ipv4Addr::LOCALHOST, port))),
+        Err(_) => value.parse(),
+    }
+}
@@ -1,72 +1,192 @@
 use async_trait::async_trait;
 use std::path::Path;
 use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
 use which::which;
 
 use crate::{Result, McpImageError};
-use super::ImageConverter;
+use super::{
+    AvifOptions, ConversionRequest, EncoderOptions, ImageConverter, ImageFormat, JxlOptions, Preview, WebpOptions,
+};
+use super::process::{converter_failure, run_cancellable};
 
 pub struct DarktableConverter;
 
+impl Default for DarktableConverter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DarktableConverter {
     pub fn new() -> Self {
         Self
     }
+    
+    /// Export settings overriding darktable's WebP defaults for this run.
+    /// darktable only has a compression type and a quality.
+    fn webp_conf(options: &WebpOptions) -> Result<Vec<String>> {
+        Self::reject_unsupported("webp", &[
+            ("near_lossless", options.near_lossless.is_some()),
+            ("alpha_quality", options.alpha_quality.is_some()),
+            ("target_size", options.target_size.is_some()),
+        ])?;
+        
+        // comp_type 0 is lossy, 1 lossless
+        let mut conf = vec![format!("plugins/imageio/format/webp/comp_type={}", u8::from(options.lossless))];
+        if let Some(quality) = options.quality {
+            conf.push(format!("plugins/imageio/format/webp/quality={}", quality));
+        }
+        Ok(conf)
+    }
+    
+    /// AVIF export settings: compression type, quality and bit depth
+    fn avif_conf(options: &AvifOptions) -> Result<Vec<String>> {
+        Self::reject_unsupported("avif", &[
+            ("speed", options.speed.is_some()),
+            ("chroma", options.chroma.is_some()),
+        ])?;
+        
+        // compression_type 0 is lossless, 1 lossy
+        let mut conf = vec![format!(
+            "plugins/imageio/format/avif/compression_type={}",
+            u8::from(!options.lossless)
+        )];
+        if let Some(quality) = options.quality {
+            conf.push(format!("plugins/imageio/format/avif/quality={}", quality));
+        }
+        if let Some(depth) = options.bit_depth {
+            conf.push(format!("plugins/imageio/format/avif/bpp={}", depth));
+        }
+        Ok(conf)
+    }
+    
+    /// JPEG XL export settings: quality (100 is lossless), effort and bit depth
+    fn jxl_conf(options: &JxlOptions) -> Result<Vec<String>> {
+        Self::reject_unsupported("jxl", &[("distance", options.distance.is_some())])?;
+        
+        let mut conf = Vec::new();
+        let quality = if options.lossless { Some(100) } else { options.quality };
+        if let Some(quality) = quality {
+            conf.push(format!("plugins/imageio/format/jxl/quality={}", quality));
+        }
+        if let Some(effort) = options.effort {
+            conf.push(format!("plugins/imageio/format/jxl/effort={}", effort));
+        }
+        if let Some(depth) = options.bit_depth {
+            conf.push(format!("plugins/imageio/format/jxl/bpp={}", depth));
+        }
+        Ok(conf)
+    }
+    
+    /// `--conf` overrides for the output format's encoder settings
+    fn export_conf(format: ImageFormat, options: &EncoderOptions) -> Result<Vec<String>> {
+        match format {
+            ImageFormat::Webp => Self::webp_conf(&options.webp()),
+            ImageFormat::Avif => Self::avif_conf(&options.avif()),
+            ImageFormat::Jxl => Self::jxl_conf(&options.jxl()),
+            _ => Ok(Vec::new()),
+        }
+    }
+    
+    /// Whether darktable can write `format` with these encoder settings
+    pub fn can_export(&self, format: ImageFormat, options: &EncoderOptions) -> bool {
+        self.supports(ImageFormat::Tiff, format) && Self::export_conf(format, options).is_ok()
+    }
+    
+    fn reject_unsupported(format: &str, options: &[(&str, bool)]) -> Result<()> {
+        let unsupported: Vec<String> = options
+            .iter()
+            .filter(|(_, set)| *set)
+            .map(|(name, _)| format!("{}.{}", format, name))
+            .collect();
+        if unsupported.is_empty() {
+            return Ok(());
+        }
+        Err(McpImageError::InvalidInput(format!(
+            "darktable does not support {}",
+            unsupported.join(", ")
+        )))
+    }
 }
 
 #[async_trait]
 impl ImageConverter for DarktableConverter {
-    async fn convert_dng_to_webp(&self, input: &Path, output: &Path) -> Result<()> {
-        // Validate input file exists
-        if !input.exists() {
-            return Err(McpImageError::FileNotFound(
-                input.display().to_string()
-            ));
-        }
-        
-        // Ensure input has .dng extension
-        if input.extension().and_then(|s| s.to_str()) != Some("dng") &&
-           input.extension().and_then(|s| s.to_str()) != Some("DNG") {
-            return Err(McpImageError::InvalidInput(
-                "Input file must be a DNG file".to_string()
-            ));
-        }
+    async fn convert(&self, request: &ConversionRequest<'_>, cancel: &CancellationToken) -> Result<()> {
+        request.prepare().await?;
         
-        // Create output directory if it doesn't exist
-        if let Some(parent) = output.parent() {
-            tokio::fs::create_dir_all(parent).await?;
+        // darktable-cli picks the output format from the file extension
+        if ImageFormat::from_path(request.output) != Some(request.output_format) {
+            return Err(McpImageError::InvalidInput(format!(
+                "darktable needs a .{} output path to write {}",
+                request.output_format.extension(),
+                request.output_format
+            )));
         }
         
         // Build and execute the command
-        let output_result = Command::new("darktable-cli")
-            .arg(input)
-            .arg(output)
-            .output()
-            .await?;
+        let mut command = Command::new("darktable-cli");
+        command
+            .arg(request.input)
+            .arg(request.output);
+        let conf = Self::export_conf(request.output_format, &request.options)?;
+        if !conf.is_empty() {
+            command.arg("--core");
+            for setting in conf {
+                command.arg("--conf").arg(setting);
+            }
+        }
+        let output_result = run_cancellable(command, request.output, cancel).await?;
         
         if output_result.status.success() {
             tracing::info!(
                 "Successfully converted {} to {} using darktable",
-                input.display(),
-                output.display()
+                request.input.display(),
+                request.output.display()
             );
             Ok(())
         } else {
-            let stderr = String::from_utf8_lossy(&output_result.stderr);
-            let stdout = String::from_utf8_lossy(&output_result.stdout);
-            
             // darktable-cli sometimes outputs to stdout instead of stderr
-            let error_msg = if stderr.is_empty() {
-                stdout.to_string()
-            } else {
-                stderr.to_string()
-            };
-            
-            Err(McpImageError::ConversionFailed(format!(
-                "darktable-cli conversion failed: {}",
-                error_msg
-            )))
+            Err(converter_failure(self.name(), &output_result))
+        }
+    }
+    
+    fn supports(&self, input: ImageFormat, output: ImageFormat) -> bool {
+        let readable = !matches!(input, ImageFormat::Gif | ImageFormat::Bmp);
+        let writable = matches!(
+            output,
+            ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Tiff | ImageFormat::Webp | ImageFormat::Avif | ImageFormat::Jxl
+        );
+        readable && writable
+    }
+    
+    async fn create_preview(
+        &self,
+        input: &Path,
+        _output: &Path,
+        max_dimension: u32,
+        cancel: &CancellationToken,
+    ) -> Result<Preview> {
+        // Develop the RAW again at preview size rather than decoding the output
+        let dir = tempfile::tempdir()?;
+        let preview = dir.path().join("preview.jpg");
+        
+        let mut command = Command::new("darktable-cli");
+        command
+            .arg(input)
+            .arg(&preview)
+            .arg("--width")
+            .arg(max_dimension.to_string())
+            .arg("--height")
+            .arg(max_dimension.to_string())
+            .arg("--hq")
+            .arg("false");
+        let result = run_cancellable(command, &preview, cancel).await?;
+        
+        if !result.status.success() {
+            return Err(converter_failure(self.name(), &result));
         }
+        Preview::read(&preview, "image/jpeg").await
     }
     
     fn is_available(&self) -> bool {
@@ -80,4 +200,95 @@ impl ImageConverter for DarktableConverter {
     fn priority(&self) -> u8 {
         40 // Lower priority than ImageMagick (slower but better for RAW)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn webp_conf_sets_compression_type_and_quality() {
+        let lossless = DarktableConverter::webp_conf(&WebpOptions::default()).unwrap();
+        assert_eq!(lossless, ["plugins/imageio/format/webp/comp_type=1"]);
+        
+        let lossy = WebpOptions {
+            lossless: false,
+            quality: Some(85),
+            ..WebpOptions::default()
+        };
+        assert_eq!(DarktableConverter::webp_conf(&lossy).unwrap(), [
+            "plugins/imageio/format/webp/comp_type=0",
+            "plugins/imageio/format/webp/quality=85",
+        ]);
+    }
+    
+    #[test]
+    fn webp_conf_rejects_settings_darktable_lacks() {
+        let options = WebpOptions {
+            lossless: false,
+            alpha_quality: Some(50),
+            target_size: Some(1000),
+            ..WebpOptions::default()
+        };
+        
+        let error = DarktableConverter::webp_conf(&options).unwrap_err();
+        
+        assert_eq!(error.to_string(), "Invalid input: darktable does not support webp.alpha_quality, webp.target_size");
+    }
+    
+    #[test]
+    fn avif_conf_sets_compression_quality_and_depth() {
+        let options = AvifOptions {
+            quality: Some(70),
+            bit_depth: Some(10),
+            ..AvifOptions::default()
+        };
+        assert_eq!(DarktableConverter::avif_conf(&options).unwrap(), [
+            "plugins/imageio/format/avif/compression_type=1",
+            "plugins/imageio/format/avif/quality=70",
+            "plugins/imageio/format/avif/bpp=10",
+        ]);
+        
+        let lossless = AvifOptions {
+            lossless: true,
+            ..AvifOptions::default()
+        };
+        assert_eq!(DarktableConverter::avif_conf(&lossless).unwrap(), [
+            "plugins/imageio/format/avif/compression_type=0",
+        ]);
+        
+        let speed = AvifOptions {
+            speed: Some(4),
+            ..AvifOptions::default()
+        };
+        assert!(DarktableConverter::avif_conf(&speed).is_err());
+    }
+    
+    #[test]
+    fn jxl_conf_sets_quality_effort_and_depth() {
+        let options = JxlOptions {
+            quality: Some(90),
+            effort: Some(5),
+            bit_depth: Some(12),
+            ..JxlOptions::default()
+        };
+        assert_eq!(DarktableConverter::jxl_conf(&options).unwrap(), [
+            "plugins/imageio/format/jxl/quality=90",
+            "plugins/imageio/format/jxl/effort=5",
+            "plugins/imageio/format/jxl/bpp=12",
+        ]);
+        
+        // darktable has no distance; lossless is quality 100
+        let lossless = JxlOptions {
+            lossless: true,
+            ..JxlOptions::default()
+        };
+        assert_eq!(DarktableConverter::jxl_conf(&lossless).unwrap(), ["plugins/imageio/format/jxl/quality=100"]);
+        
+        let distance = JxlOptions {
+            distance: Some(1.0),
+            ..JxlOptions::default()
+        };
+        assert!(DarktableConverter::jxl_conf(&distance).is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::path::Path;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Image file formats the converters read or write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Png,
+    #[serde(alias = "jpg")]
+    Jpeg,
+    #[serde(alias = "tif")]
+    Tiff,
+    Webp,
+    Avif,
+    // JPEG XL
+    Jxl,
+    #[serde(alias = "heif")]
+    Heic,
+    Gif,
+    Bmp,
+    // Adobe Digital Negative (RAW); input only
+    Dng,
+}
+
+impl ImageFormat {
+    /// Formats a conversion can produce
+    pub const OUTPUTS: &'static [ImageFormat] = &[
+        ImageFormat::Png,
+        ImageFormat::Jpeg,
+        ImageFormat::Tiff,
+        ImageFormat::Webp,
+        ImageFormat::Avif,
+        ImageFormat::Jxl,
+        ImageFormat::Heic,
+        ImageFormat::Gif,
+        ImageFormat::Bmp,
+    ];
+    
+    /// Format for a file extension (case-insensitive, without the dot)
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" | "jpe" => Some(Self::Jpeg),
+            "tif" | "tiff" => Some(Self::Tiff),
+            "webp" => Some(Self::Webp),
+            "avif" => Some(Self::Avif),
+            "jxl" => Some(Self::Jxl),
+            "heic" | "heif" => Some(Self::Heic),
+            "gif" => Some(Self::Gif),
+            "bmp" => Some(Self::Bmp),
+            "dng" => Some(Self::Dng),
+            _ => None,
+        }
+    }
+    
+    /// Format implied by a path's extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Self::from_extension)
+    }
+    
+    /// Preferred file extension
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Tiff => "tif",
+            Self::Webp => "webp",
+            Self::Avif => "avif",
+            Self::Jxl => "jxl",
+            Self::Heic => "heic",
+            Self::Gif => "gif",
+            Self::Bmp => "bmp",
+            Self::Dng => "dng",
+        }
+    }
+    
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Tiff => "image/tiff",
+            Self::Webp => "image/webp",
+            Self::Avif => "image/avif",
+            Self::Jxl => "image/jxl",
+            Self::Heic => "image/heic",
+            Self::Gif => "image/gif",
+            Self::Bmp => "image/bmp",
+            Self::Dng => "image/x-adobe-dng",
+        }
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Png => "PNG",
+            Self::Jpeg => "JPEG",
+            Self::Tiff => "TIFF",
+            Self::Webp => "WebP",
+            Self::Avif => "AVIF",
+            Self::Jxl => "JPEG XL",
+            Self::Heic => "HEIC",
+            Self::Gif => "GIF",
+            Self::Bmp => "BMP",
+            Self::Dng => "DNG",
+        };
+        f.write_str(name)
+    }
+}
@@ -1,86 +1,485 @@
 use async_trait::async_trait;
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::path::Path;
 use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
 use which::which;
 
-use crate::{Result, McpImageError};
-use super::ImageConverter;
+use crate::Result;
+use super::{
+    AvifOptions, ChromaSubsampling, ColorConvert, Colorspace, ConversionRequest, Crop, Gravity, ImageConverter,
+    ImageFormat, JxlOptions, Preview, Resize, ResizeFilter, ResizeMode, Step, Transform, Watermark, WebpOptions,
+};
+use super::process::{converter_failure, run_cancellable};
 
-pub struct ImageMagickConverter {
-    command: String,
+pub struct ImageMagickConverter;
+
+impl Default for ImageMagickConverter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ImageMagickConverter {
     pub fn new() -> Self {
-        // Try to find convert7 first, then fall back to magick
-        let command = if which("convert7").is_ok() {
-            "convert7".to_string()
-        } else if which("magick").is_ok() {
-            "magick".to_string()
-        } else {
-            "convert7".to_string() // Default, will fail in is_available
+        Self
+    }
+    
+    /// ImageMagick's coder name for a format
+    fn coder(format: ImageFormat) -> &'static str {
+        match format {
+            ImageFormat::Png => "PNG",
+            ImageFormat::Jpeg => "JPEG",
+            ImageFormat::Tiff => "TIFF",
+            ImageFormat::Webp => "WEBP",
+            ImageFormat::Avif => "AVIF",
+            ImageFormat::Jxl => "JXL",
+            ImageFormat::Heic => "HEIC",
+            ImageFormat::Gif => "GIF",
+            ImageFormat::Bmp => "BMP",
+            ImageFormat::Dng => "DNG",
+        }
+    }
+    
+    /// Output argument with an explicit coder prefix, so the format does not
+    /// depend on the file extension
+    fn output_spec(output: &Path, format: ImageFormat) -> OsString {
+        let mut spec = OsString::from(Self::coder(format));
+        spec.push(":");
+        spec.push(output);
+        spec
+    }
+    
+    /// Formats this ImageMagick build lacks a delegate library for: outputs
+    /// it cannot write, and DNG if it cannot read RAW files
+    pub async fn missing_delegates(&self) -> Result<Vec<ImageFormat>> {
+        let output = Command::new(Self::command())
+            .arg("-list")
+            .arg("format")
+            .kill_on_drop(true)
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(converter_failure(self.name(), &output));
+        }
+        
+        Ok(Self::missing_from_listing(&String::from_utf8_lossy(&output.stdout)))
+    }
+    
+    /// Parse `-list format` output into the formats `missing_delegates`
+    /// reports
+    fn missing_from_listing(listing: &str) -> Vec<ImageFormat> {
+        // Lines look like "     AVIF  HEIC      rw+   AV1 Image File Format";
+        // ImageMagick 6 has no module column
+        let modes: HashMap<&str, &str> = listing
+            .lines()
+            .filter_map(|line| {
+                let mut tokens = line.split_whitespace();
+                let name = tokens.next()?.trim_end_matches('*');
+                let mode = tokens.take(2).find(|t| {
+                    t.len() == 3 && t.chars().all(|c| matches!(c, 'r' | 'w' | '+' | '-'))
+                })?;
+                Some((name, mode))
+            })
+            .collect();
+        
+        let has_mode = |format: ImageFormat, mode: char| {
+            modes.get(Self::coder(format)).is_some_and(|m| m.contains(mode))
         };
+        let mut missing: Vec<ImageFormat> = ImageFormat::OUTPUTS
+            .iter()
+            .copied()
+            .filter(|format| !has_mode(*format, 'w'))
+            .collect();
+        if !has_mode(ImageFormat::Dng, 'r') {
+            missing.push(ImageFormat::Dng);
+        }
+        missing
+    }
+    
+    /// `-quality` and `-define webp:*` arguments for the encoder settings
+    fn webp_args(command: &mut Command, options: &WebpOptions) {
+        let mut defines = vec![
+            format!("webp:lossless={}", options.lossless),
+            format!("webp:method={}", options.method),
+            "webp:partition-limit=0".to_string(),
+        ];
+        if options.lossless {
+            // Keep RGB values under fully transparent pixels
+            defines.push("webp:exact=true".to_string());
+        }
+        if let Some(level) = options.near_lossless {
+            defines.push(format!("webp:near-lossless={}", level));
+        }
+        if let Some(quality) = options.alpha_quality {
+            defines.push(format!("webp:alpha-quality={}", quality));
+        }
+        if let Some(size) = options.target_size {
+            defines.push(format!("webp:target-size={}", size));
+        }
         
-        Self { command }
+        if let Some(quality) = options.quality {
+            command.arg("-quality").arg(quality.to_string());
+        }
+        for define in defines {
+            command.arg("-define").arg(define);
+        }
     }
-}
-
-#[async_trait]
-impl ImageConverter for ImageMagickConverter {
-    async fn convert_dng_to_webp(&self, input: &Path, output: &Path) -> Result<()> {
-        // Validate input file exists
-        if !input.exists() {
-            return Err(McpImageError::FileNotFound(
-                input.display().to_string()
-            ));
+    
+    /// AVIF settings for the libheif coder, which encodes losslessly at
+    /// quality 100
+    fn avif_args(command: &mut Command, options: &AvifOptions) {
+        let quality = if options.lossless { Some(100) } else { options.quality };
+        let chroma = if options.lossless { Some(ChromaSubsampling::Yuv444) } else { options.chroma };
+        
+        if let Some(quality) = quality {
+            command.arg("-quality").arg(quality.to_string());
+        }
+        if let Some(depth) = options.bit_depth {
+            command.arg("-depth").arg(depth.to_string());
         }
+        if let Some(speed) = options.speed {
+            command.arg("-define").arg(format!("heic:speed={}", speed));
+        }
+        if let Some(chroma) = chroma {
+            command.arg("-define").arg(format!("heic:chroma={}", chroma.as_str()));
+        }
+    }
+    
+    /// JPEG XL settings; distance 0 (or quality 100 on builds without
+    /// `jxl:distance`) is lossless
+    fn jxl_args(command: &mut Command, options: &JxlOptions) {
+        let quality = if options.lossless { Some(100) } else { options.quality };
+        let distance = if options.lossless { Some(0.0) } else { options.distance };
         
-        // Ensure input has .dng extension
-        if input.extension().and_then(|s| s.to_str()) != Some("dng") &&
-           input.extension().and_then(|s| s.to_str()) != Some("DNG") {
-            return Err(McpImageError::InvalidInput(
-                "Input file must be a DNG file".to_string()
-            ));
+        if let Some(quality) = quality {
+            command.arg("-quality").arg(quality.to_string());
+        }
+        if let Some(depth) = options.bit_depth {
+            command.arg("-depth").arg(depth.to_string());
+        }
+        if let Some(distance) = distance {
+            command.arg("-define").arg(format!("jxl:distance={}", distance));
         }
+        if let Some(effort) = options.effort {
+            command.arg("-define").arg(format!("jxl:effort={}", effort));
+        }
+    }
+    
+    /// Settings for the output format's encoder
+    fn encoder_args(command: &mut Command, request: &ConversionRequest<'_>) {
+        match request.output_format {
+            ImageFormat::Webp => Self::webp_args(command, &request.options.webp()),
+            ImageFormat::Avif => Self::avif_args(command, &request.options.avif()),
+            ImageFormat::Jxl => Self::jxl_args(command, &request.options.jxl()),
+            _ => {}
+        }
+    }
+    
+    fn filter_name(filter: ResizeFilter) -> &'static str {
+        match filter {
+            ResizeFilter::Point => "Point",
+            ResizeFilter::Box => "Box",
+            ResizeFilter::Triangle => "Triangle",
+            ResizeFilter::Catrom => "Catrom",
+            ResizeFilter::Mitchell => "Mitchell",
+            ResizeFilter::Lanczos => "Lanczos",
+            ResizeFilter::Gaussian => "Gaussian",
+        }
+    }
+    
+    fn gravity_name(gravity: Gravity) -> &'static str {
+        match gravity {
+            Gravity::NorthWest => "NorthWest",
+            Gravity::North => "North",
+            Gravity::NorthEast => "NorthEast",
+            Gravity::West => "West",
+            Gravity::Center => "Center",
+            Gravity::East => "East",
+            Gravity::SouthWest => "SouthWest",
+            Gravity::South => "South",
+            Gravity::SouthEast => "SouthEast",
+        }
+    }
+    
+    /// `-crop`, anchored at the gravity if one is given. `+repage` drops the
+    /// virtual canvas so later operations see a plain image.
+    fn crop_args(command: &mut Command, crop: &Crop) {
+        if let Some(gravity) = crop.gravity {
+            command.arg("-gravity").arg(Self::gravity_name(gravity));
+        }
+        command
+            .arg("-crop")
+            .arg(format!("{}x{}{:+}{:+}", crop.width, crop.height, crop.x, crop.y))
+            .arg("+repage");
+        if crop.gravity.is_some() {
+            command.arg("+gravity");
+        }
+    }
+    
+    fn resize_args(command: &mut Command, resize: &Resize) {
+        if let Some(filter) = resize.filter {
+            command.arg("-filter").arg(Self::filter_name(filter));
+        }
+        
+        let mut geometry = match (resize.width, resize.height) {
+            (Some(width), Some(height)) => format!("{}x{}", width, height),
+            (Some(width), None) => width.to_string(),
+            (None, Some(height)) => format!("x{}", height),
+            (None, None) => return,
+        };
+        match resize.mode {
+            ResizeMode::Fit => {}
+            ResizeMode::Fill => geometry.push('^'),
+            ResizeMode::Exact => geometry.push('!'),
+        }
+        if resize.only_shrink {
+            geometry.push('>');
+        }
+        command.arg("-resize").arg(&geometry);
+        
+        if resize.mode == ResizeMode::Fill {
+            // Trim the overflow evenly from both sides. An image left
+            // smaller than the box by only_shrink must not be padded out
+            // to it, so it is cropped, which never enlarges, not extended.
+            let (Some(width), Some(height)) = (resize.width, resize.height) else {
+                return;
+            };
+            command.arg("-gravity").arg("Center");
+            if resize.only_shrink {
+                command.arg("-crop").arg(format!("{}x{}+0+0", width, height)).arg("+repage");
+            } else {
+                command.arg("-extent").arg(format!("{}x{}", width, height));
+            }
+            command.arg("+gravity");
+        }
+    }
+    
+    /// Geometry operators for a transform, in the order it documents
+    fn transform_args(command: &mut Command, transform: &Transform) {
+        if transform.auto_orient {
+            command.arg("-auto-orient");
+        }
+        if let Some(crop) = &transform.crop {
+            Self::crop_args(command, crop);
+        }
+        if let Some(resize) = &transform.resize {
+            Self::resize_args(command, resize);
+        }
+        if let Some(degrees) = transform.rotate {
+            command.arg("-rotate").arg(degrees.to_string());
+        }
+        if transform.flip {
+            command.arg("-flip");
+        }
+        if transform.flop {
+            command.arg("-flop");
+        }
+    }
+    
+    fn colorspace_name(colorspace: Colorspace) -> &'static str {
+        match colorspace {
+            Colorspace::Srgb => "sRGB",
+            Colorspace::LinearRgb => "RGB",
+            Colorspace::Gray => "Gray",
+            Colorspace::Cmyk => "CMYK",
+            Colorspace::Lab => "Lab",
+        }
+    }
+    
+    fn color_convert_args(command: &mut Command, convert: &ColorConvert) {
+        if let Some(profile) = &convert.profile {
+            command.arg("-profile").arg(profile);
+        }
+        if let Some(colorspace) = convert.colorspace {
+            command.arg("-colorspace").arg(Self::colorspace_name(colorspace));
+        }
+    }
+    
+    /// Composite the watermark, faded to its opacity, at the gravity corner
+    fn watermark_args(command: &mut Command, watermark: &Watermark) {
+        command
+            .arg("(")
+            .arg(&watermark.image)
+            .arg("-alpha")
+            .arg("set")
+            .arg("-channel")
+            .arg("A")
+            .arg("-evaluate")
+            .arg("multiply")
+            .arg((f64::from(watermark.opacity) / 100.0).to_string())
+            .arg("+channel");
+        if let Some(width) = watermark.width {
+            command.arg("-resize").arg(width.to_string());
+        }
+        command
+            .arg(")")
+            .arg("-gravity")
+            .arg(Self::gravity_name(watermark.gravity))
+            .arg("-geometry")
+            .arg(format!("+{0}+{0}", watermark.margin))
+            .arg("-composite")
+            .arg("+gravity");
+    }
+    
+    /// Operators for pipeline steps; `develop` and `encode` are handled by
+    /// the caller and the encoder settings
+    fn step_args(command: &mut Command, step: &Step) {
+        match step {
+            Step::Develop | Step::Encode(_) => {}
+            Step::Resize(resize) => Self::resize_args(command, resize),
+            Step::Sharpen(sharpen) => {
+                command.arg("-unsharp").arg(format!(
+                    "{}x{}+{}+{}",
+                    sharpen.radius, sharpen.sigma, sharpen.amount, sharpen.threshold
+                ));
+            }
+            Step::ColorConvert(convert) => Self::color_convert_args(command, convert),
+            Step::Watermark(watermark) => Self::watermark_args(command, watermark),
+        }
+    }
+    
+    /// Apply `transform` and encode to `request.output_format` in a single
+    /// invocation. Returns the final width and height, if ImageMagick
+    /// reported them.
+    pub async fn transform(
+        &self,
+        request: &ConversionRequest<'_>,
+        transform: &Transform,
+        cancel: &CancellationToken,
+    ) -> Result<Option<(u32, u32)>> {
+        self.run_operators(request, cancel, |command| Self::transform_args(command, transform)).await
+    }
+    
+    /// Run pipeline `steps` and encode in a single invocation, like
+    /// `transform`
+    pub async fn process(
+        &self,
+        request: &ConversionRequest<'_>,
+        steps: &[Step],
+        cancel: &CancellationToken,
+    ) -> Result<Option<(u32, u32)>> {
+        self.run_operators(request, cancel, |command| {
+            for step in steps {
+                Self::step_args(command, step);
+            }
+        })
+        .await
+    }
+    
+    /// Read the input, add the operators, encode, and report the final
+    /// dimensions printed by `-print`
+    async fn run_operators(
+        &self,
+        request: &ConversionRequest<'_>,
+        cancel: &CancellationToken,
+        operators: impl FnOnce(&mut Command),
+    ) -> Result<Option<(u32, u32)>> {
+        request.prepare().await?;
         
-        // Create output directory if it doesn't exist
-        if let Some(parent) = output.parent() {
-            tokio::fs::create_dir_all(parent).await?;
+        let mut command = Command::new(Self::command());
+        command.arg(request.input);
+        operators(&mut command);
+        Self::encoder_args(&mut command, request);
+        command
+            .arg("-print")
+            .arg("%w %h\\n")
+            .arg(Self::output_spec(request.output, request.output_format));
+        let output = run_cancellable(command, request.output, cancel).await?;
+        
+        if !output.status.success() {
+            return Err(converter_failure(self.name(), &output));
+        }
+        tracing::info!(
+            "Processed {} to {}",
+            request.input.display(),
+            request.output.display()
+        );
+        
+        Ok(Self::printed_dimensions(&String::from_utf8_lossy(&output.stdout)))
+    }
+    
+    /// Width and height from the output of `-print "%w %h\n"`, which prints
+    /// one line per frame; the first frame is the image
+    fn printed_dimensions(stdout: &str) -> Option<(u32, u32)> {
+        stdout.lines().find_map(|line| {
+            let (width, height) = line.trim().split_once(' ')?;
+            Some((width.parse().ok()?, height.parse().ok()?))
+        })
+    }
+    
+    /// `convert7` if installed, otherwise `magick`. Looked up on every use so
+    /// a binary installed while the server runs is picked up.
+    fn command() -> &'static str {
+        if which("convert7").is_ok() {
+            "convert7"
+        } else {
+            "magick"
         }
+    }
+}
+
+#[async_trait]
+impl ImageConverter for ImageMagickConverter {
+    async fn convert(&self, request: &ConversionRequest<'_>, cancel: &CancellationToken) -> Result<()> {
+        request.prepare().await?;
         
         // Build and execute the command
-        let output_result = Command::new(&self.command)
-            .arg(input)
-            .arg("-define")
-            .arg("webp:lossless=true")
-            .arg("-define")
-            .arg("webp:exact=true")
-            .arg("-define")
-            .arg("webp:method=6")
-            .arg("-define")
-            .arg("webp:partition-limit=0")
-            .arg(output)
-            .output()
-            .await?;
+        let mut command = Command::new(Self::command());
+        command.arg(request.input);
+        Self::encoder_args(&mut command, request);
+        command.arg(Self::output_spec(request.output, request.output_format));
+        let output_result = run_cancellable(command, request.output, cancel).await?;
         
         if output_result.status.success() {
             tracing::info!(
                 "Successfully converted {} to {}",
-                input.display(),
-                output.display()
+                request.input.display(),
+                request.output.display()
             );
             Ok(())
         } else {
-            let stderr = String::from_utf8_lossy(&output_result.stderr);
-            Err(McpImageError::ConversionFailed(format!(
-                "ImageMagick conversion failed: {}",
-                stderr
-            )))
+            Err(converter_failure(self.name(), &output_result))
         }
     }
     
+    fn supports(&self, _input: ImageFormat, output: ImageFormat) -> bool {
+        // Reads everything it has a delegate for, RAW included
+        output != ImageFormat::Dng
+    }
+    
+    async fn create_preview(
+        &self,
+        _input: &Path,
+        output: &Path,
+        max_dimension: u32,
+        cancel: &CancellationToken,
+    ) -> Result<Preview> {
+        // Scaling the finished WebP is much cheaper than decoding the RAW again
+        let dir = tempfile::tempdir()?;
+        let preview = dir.path().join("preview.webp");
+        
+        let mut command = Command::new(Self::command());
+        command
+            .arg(output)
+            .arg("-auto-orient")
+            .arg("-thumbnail")
+            .arg(format!("{0}x{0}>", max_dimension))
+            .arg("-strip")
+            .arg("-quality")
+            .arg("75")
+            .arg(&preview);
+        let result = run_cancellable(command, &preview, cancel).await?;
+        
+        if !result.status.success() {
+            return Err(converter_failure(self.name(), &result));
+        }
+        Preview::read(&preview, "image/webp").await
+    }
+    
     fn is_available(&self) -> bool {
-        which(&self.command).is_ok()
+        which(Self::command()).is_ok()
     }
     
     fn name(&self) -> &'static str {
@@ -90,4 +489,352 @@ impl ImageConverter for ImageMagickConverter {
     fn priority(&self) -> u8 {
         60 // Higher priority than darktable (faster)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converters::Sharpen;
+    
+    /// Arguments `build` adds to an empty command
+    fn args(build: impl FnOnce(&mut Command)) -> Vec<String> {
+        let mut command = Command::new("magick");
+        build(&mut command);
+        command.as_std().get_args().map(|arg| arg.to_string_lossy().into_owned()).collect()
+    }
+    
+    #[test]
+    fn webp_defaults_are_lossless_at_the_slowest_method() {
+        let argv = args(|c| ImageMagickConverter::webp_args(c, &WebpOptions::default()));
+        
+        assert_eq!(argv, [
+            "-define", "webp:lossless=true",
+            "-define", "webp:method=6",
+            "-define", "webp:partition-limit=0",
+            "-define", "webp:exact=true",
+        ]);
+    }
+    
+    #[test]
+    fn webp_lossy_settings_become_quality_and_defines() {
+        let options = WebpOptions {
+            lossless: false,
+            quality: Some(80),
+            alpha_quality: Some(90),
+            method: 4,
+            target_size: Some(50_000),
+            ..WebpOptions::default()
+        };
+        
+        let argv = args(|c| ImageMagickConverter::webp_args(c, &options));
+        
+        assert_eq!(argv, [
+            "-quality", "80",
+            "-define", "webp:lossless=false",
+            "-define", "webp:method=4",
+            "-define", "webp:partition-limit=0",
+            "-define", "webp:alpha-quality=90",
+            "-define", "webp:target-size=50000",
+        ]);
+    }
+    
+    #[test]
+    fn webp_near_lossless_keeps_exact_pixels() {
+        let options = WebpOptions {
+            near_lossless: Some(60),
+            ..WebpOptions::default()
+        };
+        
+        let argv = args(|c| ImageMagickConverter::webp_args(c, &options));
+        
+        assert!(argv.contains(&"webp:exact=true".to_string()));
+        assert!(argv.contains(&"webp:near-lossless=60".to_string()));
+    }
+    
+    #[test]
+    fn avif_settings_become_quality_depth_and_heic_defines() {
+        let options = AvifOptions {
+            quality: Some(60),
+            speed: Some(6),
+            chroma: Some(ChromaSubsampling::Yuv420),
+            bit_depth: Some(10),
+            ..AvifOptions::default()
+        };
+        
+        let argv = args(|c| ImageMagickConverter::avif_args(c, &options));
+        
+        assert_eq!(argv, [
+            "-quality", "60",
+            "-depth", "10",
+            "-define", "heic:speed=6",
+            "-define", "heic:chroma=420",
+        ]);
+    }
+    
+    #[test]
+    fn lossless_avif_is_quality_100_with_full_chroma() {
+        let options = AvifOptions {
+            lossless: true,
+            ..AvifOptions::default()
+        };
+        
+        let argv = args(|c| ImageMagickConverter::avif_args(c, &options));
+        
+        assert_eq!(argv, ["-quality", "100", "-define", "heic:chroma=444"]);
+        assert!(args(|c| ImageMagickConverter::avif_args(c, &AvifOptions::default())).is_empty());
+    }
+    
+    #[test]
+    fn jxl_settings_become_quality_depth_and_jxl_defines() {
+        let options = JxlOptions {
+            distance: Some(1.5),
+            effort: Some(7),
+            bit_depth: Some(16),
+            ..JxlOptions::default()
+        };
+        
+        let argv = args(|c| ImageMagickConverter::jxl_args(c, &options));
+        
+        assert_eq!(argv, [
+            "-depth", "16",
+            "-define", "jxl:distance=1.5",
+            "-define", "jxl:effort=7",
+        ]);
+    }
+    
+    #[test]
+    fn lossless_jxl_is_distance_zero_and_quality_100() {
+        let options = JxlOptions {
+            lossless: true,
+            ..JxlOptions::default()
+        };
+        
+        let argv = args(|c| ImageMagickConverter::jxl_args(c, &options));
+        
+        assert_eq!(argv, ["-quality", "100", "-define", "jxl:distance=0"]);
+    }
+    
+    /// `magick -list format` from ImageMagick 7, trimmed
+    const IM7_FORMATS: &str = "   Format  Module    Mode  Description
+-------------------------------------------------------------------------------
+      3FR  DNG       r--   Hasselblad CFV/H3D39II Raw Format (0.21.2-3.1)
+     AVIF  HEIC      rw+   AV1 Image File Format (1.17.6)
+      BMP* BMP       rw-   Microsoft Windows bitmap image
+      DNG  DNG       r--   Digital Negative Raw Format (0.21.2-3.1)
+      GIF* GIF       rw+   CompuServe graphics interchange format
+     HEIC  HEIC      rw+   High Efficiency Image Format (1.17.6)
+     JPEG* JPEG      rw-   Joint Photographic Experts Group JFIF format (libjpeg-turbo 2.1.5)
+      PNG* PNG       rw-   Portable Network Graphics (libpng 1.6.43)
+     TIFF* TIFF      rw+   Tagged Image File Format (LIBTIFF, Version 4.5.1)
+     WEBP* WEBP      rw+   WebP Image Format (libwebp 1.3.2 [020F])
+
+* native blob support
+r read support
+w write support
++ support for multiple images
+";
+    
+    /// `convert -list format` from ImageMagick 6, trimmed
+    const IM6_FORMATS: &str = "   Format  Mode  Description
+-------------------------------------------------------------------------------
+      3FR  r--   Hasselblad CFV/H3D39II
+      BMP* rw-   Microsoft Windows bitmap image
+      DNG  r--   Digital Negative
+      GIF* rw+   CompuServe graphics interchange format
+     HEIC  r--   Apple High efficiency Image Format
+     JPEG* rw-   Joint Photographic Experts Group JFIF format (80)
+      PNG* rw-   Portable Network Graphics (libpng 1.6.37)
+     TIFF* rw+   Tagged Image File Format (LIBTIFF, Version 4.1.0)
+     WEBP* rw+   WebP Image Format (libwebp 1.0.3[0208])
+
+* native blob support
+r read support
+w write support
++ support for multiple images
+";
+    
+    #[test]
+    fn format_listing_from_imagemagick_7() {
+        let missing = ImageMagickConverter::missing_from_listing(IM7_FORMATS);
+        
+        assert_eq!(missing, [ImageFormat::Jxl]);
+    }
+    
+    #[test]
+    fn format_listing_from_imagemagick_6() {
+        let missing = ImageMagickConverter::missing_from_listing(IM6_FORMATS);
+        
+        assert_eq!(missing, [ImageFormat::Avif, ImageFormat::Jxl, ImageFormat::Heic]);
+    }
+    
+    #[test]
+    fn format_listing_without_raw_support_reports_dng() {
+        let listing = IM7_FORMATS.replace("DNG       r--", "DNG       ---");
+        
+        let missing = ImageMagickConverter::missing_from_listing(&listing);
+        
+        assert_eq!(missing, [ImageFormat::Jxl, ImageFormat::Dng]);
+    }
+    
+    #[test]
+    fn crop_offsets_are_signed_and_gravity_is_reset() {
+        let crop = Crop {
+            width: 300,
+            height: 200,
+            x: 10,
+            y: -5,
+            gravity: None,
+        };
+        assert_eq!(args(|c| ImageMagickConverter::crop_args(c, &crop)), ["-crop", "300x200+10-5", "+repage"]);
+        
+        let centered = Crop {
+            x: 0,
+            y: 0,
+            gravity: Some(Gravity::Center),
+            ..crop
+        };
+        assert_eq!(args(|c| ImageMagickConverter::crop_args(c, &centered)), [
+            "-gravity", "Center", "-crop", "300x200+0+0", "+repage", "+gravity",
+        ]);
+    }
+    
+    fn resize(width: Option<u32>, height: Option<u32>, mode: ResizeMode) -> Resize {
+        Resize {
+            width,
+            height,
+            mode,
+            filter: None,
+            only_shrink: false,
+        }
+    }
+    
+    #[test]
+    fn resize_geometry_follows_the_mode() {
+        let resize_argv = |resize: Resize| args(|c| ImageMagickConverter::resize_args(c, &resize));
+        
+        assert_eq!(resize_argv(resize(Some(800), Some(600), ResizeMode::Fit)), ["-resize", "800x600"]);
+        assert_eq!(resize_argv(resize(Some(800), None, ResizeMode::Fit)), ["-resize", "800"]);
+        assert_eq!(resize_argv(resize(None, Some(600), ResizeMode::Fit)), ["-resize", "x600"]);
+        assert_eq!(resize_argv(resize(Some(800), Some(600), ResizeMode::Exact)), ["-resize", "800x600!"]);
+        // Fill covers the box, then trims the overflow around the centre
+        assert_eq!(resize_argv(resize(Some(800), Some(600), ResizeMode::Fill)), [
+            "-resize", "800x600^", "-gravity", "Center", "-extent", "800x600", "+gravity",
+        ]);
+    }
+    
+    #[test]
+    fn resize_filter_and_only_shrink() {
+        let options = Resize {
+            filter: Some(ResizeFilter::Lanczos),
+            only_shrink: true,
+            ..resize(Some(800), Some(600), ResizeMode::Fill)
+        };
+        
+        let argv = args(|c| ImageMagickConverter::resize_args(c, &options));
+        
+        assert_eq!(argv, [
+            "-filter", "Lanczos",
+            "-resize", "800x600^>",
+            "-gravity", "Center", "-crop", "800x600+0+0", "+repage", "+gravity",
+        ]);
+    }
+    
+    #[test]
+    fn only_shrink_never_pads_a_small_image() {
+        for mode in [ResizeMode::Fit, ResizeMode::Fill, ResizeMode::Exact] {
+            let options = Resize {
+                only_shrink: true,
+                ..resize(Some(800), Some(600), mode)
+            };
+            
+            let argv = args(|c| ImageMagickConverter::resize_args(c, &options));
+            
+            assert!(!argv.iter().any(|arg| arg == "-extent"), "{:?}: {:?}", mode, argv);
+        }
+    }
+    
+    #[test]
+    fn transform_operators_run_in_documented_order() {
+        let transform = Transform {
+            crop: Some(Crop {
+                width: 100,
+                height: 50,
+                x: 1,
+                y: 2,
+                gravity: None,
+            }),
+            resize: Some(resize(Some(40), None, ResizeMode::Fit)),
+            rotate: Some(90.0),
+            flip: true,
+            flop: true,
+            ..Transform::default()
+        };
+        
+        let argv = args(|c| ImageMagickConverter::transform_args(c, &transform));
+        
+        assert_eq!(argv, [
+            "-auto-orient",
+            "-crop", "100x50+1+2", "+repage",
+            "-resize", "40",
+            "-rotate", "90",
+            "-flip",
+            "-flop",
+        ]);
+    }
+    
+    #[test]
+    fn transform_without_auto_orient_adds_nothing() {
+        let transform = Transform {
+            auto_orient: false,
+            ..Transform::default()
+        };
+        
+        assert!(args(|c| ImageMagickConverter::transform_args(c, &transform)).is_empty());
+    }
+    
+    #[test]
+    fn printed_dimensions_come_from_the_first_frame() {
+        assert_eq!(ImageMagickConverter::printed_dimensions("640 427\n"), Some((640, 427)));
+        assert_eq!(ImageMagickConverter::printed_dimensions("64 48\n32 24\n"), Some((64, 48)));
+        assert_eq!(ImageMagickConverter::printed_dimensions("warning: odd\n  20 10  \n"), Some((20, 10)));
+        assert_eq!(ImageMagickConverter::printed_dimensions(""), None);
+        assert_eq!(ImageMagickConverter::printed_dimensions("%w %h\n"), None);
+    }
+    
+    #[test]
+    fn watermark_is_faded_scaled_and_placed_at_its_gravity() {
+        let watermark = Watermark {
+            image: "logo.png".to_string(),
+            gravity: Gravity::SouthEast,
+            margin: 16,
+            opacity: 40,
+            width: Some(120),
+        };
+        
+        let argv = args(|c| ImageMagickConverter::watermark_args(c, &watermark));
+        
+        assert_eq!(argv, [
+            "(", "logo.png", "-alpha", "set", "-channel", "A", "-evaluate", "multiply", "0.4", "+channel",
+            "-resize", "120", ")",
+            "-gravity", "SouthEast", "-geometry", "+16+16", "-composite", "+gravity",
+        ]);
+    }
+    
+    #[test]
+    fn step_operators() {
+        let step_argv = |step: Step| args(|c| ImageMagickConverter::step_args(c, &step));
+        
+        assert_eq!(step_argv(Step::Sharpen(Sharpen::default())), ["-unsharp", "0x1+1+0.05"]);
+        assert_eq!(
+            step_argv(Step::ColorConvert(ColorConvert {
+                profile: Some("sRGB.icc".to_string()),
+                colorspace: Some(Colorspace::Gray),
+            })),
+            ["-profile", "sRGB.icc", "-colorspace", "Gray"]
+        );
+        assert_eq!(step_argv(Step::Resize(resize(None, Some(100), ResizeMode::Fit))), ["-resize", "x100"]);
+        // The caller runs these
+        assert!(step_argv(Step::Develop).is_empty());
+        assert!(step_argv(Step::Encode(Default::default())).is_empty());
+    }
 }
\ No newline at end of file
@@ -1,17 +1,95 @@
 mod imagemagick;
 mod darktable;
+mod format;
+mod options;
+mod pipeline;
+mod process;
+mod transform;
 
 pub use imagemagick::ImageMagickConverter;
 pub use darktable::DarktableConverter;
+pub use format::ImageFormat;
+pub use options::{AvifOptions, ChromaSubsampling, EncoderOptions, JxlOptions, WebpOptions};
+pub use pipeline::{ColorConvert, Colorspace, Encode, Pipeline, Sharpen, Step, Watermark};
+pub use transform::{Crop, Gravity, Resize, ResizeFilter, ResizeMode, Transform};
 
 use async_trait::async_trait;
 use std::path::Path;
-use crate::Result;
+use tokio_util::sync::CancellationToken;
+use crate::{Result, McpImageError};
+
+/// A small rendition of a converted image, returned to clients inline
+#[derive(Debug, Clone)]
+pub struct Preview {
+    pub data: Vec<u8>,
+    pub mime_type: &'static str,
+}
+
+impl Preview {
+    /// Load a preview a converter wrote to `path`
+    pub async fn read(path: &Path, mime_type: &'static str) -> Result<Self> {
+        let data = tokio::fs::read(path).await.map_err(|e| {
+            McpImageError::ConversionFailed(format!("Preview was not written: {}", e))
+        })?;
+        if data.is_empty() {
+            return Err(McpImageError::ConversionFailed("Converter produced an empty preview".to_string()));
+        }
+        Ok(Self { data, mime_type })
+    }
+}
+
+/// One file to convert, with the formats on either side
+#[derive(Debug, Clone)]
+pub struct ConversionRequest<'a> {
+    pub input: &'a Path,
+    pub input_format: ImageFormat,
+    pub output: &'a Path,
+    pub output_format: ImageFormat,
+    /// Encoder settings for the output format
+    pub options: EncoderOptions,
+}
+
+impl<'a> ConversionRequest<'a> {
+    /// Check the input exists and create the output directory
+    async fn prepare(&self) -> Result<()> {
+        if !self.input.exists() {
+            return Err(McpImageError::FileNotFound(self.input.display().to_string()));
+        }
+        if let Some(parent) = self.output.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        Ok(())
+    }
+}
 
 #[async_trait]
 pub trait ImageConverter: Send + Sync {
-    /// Convert a DNG file to WebP format
-    async fn convert_dng_to_webp(&self, input: &Path, output: &Path) -> Result<()>;
+    /// Convert `request.input` to `request.output_format`, writing
+    /// `request.output`. Only called for format pairs `supports` accepts.
+    ///
+    /// When `cancel` fires the converter process is terminated, any partial
+    /// output is removed and `McpImageError::Cancelled` is returned.
+    async fn convert(&self, request: &ConversionRequest<'_>, cancel: &CancellationToken) -> Result<()>;
+    
+    /// Whether this converter can read `input` and write `output`
+    fn supports(&self, input: ImageFormat, output: ImageFormat) -> bool;
+    
+    /// Render a preview of a finished conversion, at most `max_dimension`
+    /// pixels on its longest edge. Converters may read either the original
+    /// `input` or the converted `output`.
+    async fn create_preview(
+        &self,
+        input: &Path,
+        output: &Path,
+        max_dimension: u32,
+        cancel: &CancellationToken,
+    ) -> Result<Preview> {
+        let _ = (input, output, max_dimension, cancel);
+        Err(McpImageError::ConversionFailed(format!(
+            "{} cannot create previews",
+            self.name()
+        )))
+    }
     
     /// Check if this converter is available on the system
     fn is_available(&self) -> bool;
@@ -30,6 +108,12 @@ pub struct AutoConverter {
     converters: Vec<Box<dyn ImageConverter>>,
 }
 
+impl Default for AutoConverter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AutoConverter {
     pub fn new() -> Self {
         let mut converters: Vec<Box<dyn ImageConverter>> = vec![
@@ -50,27 +134,40 @@ impl AutoConverter {
             .map(|c| c.name())
             .collect()
     }
-}
-
-#[async_trait]
-impl ImageConverter for AutoConverter {
-    async fn convert_dng_to_webp(&self, input: &Path, output: &Path) -> Result<()> {
+    
+    /// Convert with the first available converter supporting the formats
+    /// that succeeds, returning the converter that did the work
+    pub async fn convert_with_fallback(
+        &self,
+        request: &ConversionRequest<'_>,
+        cancel: &CancellationToken,
+    ) -> Result<&dyn ImageConverter> {
         let mut last_error = None;
+        let mut any_available = false;
         
         for converter in &self.converters {
-            if converter.is_available() {
-                tracing::info!("Trying converter: {}", converter.name());
-                
-                match converter.convert_dng_to_webp(input, output).await {
-                    Ok(()) => {
-                        tracing::info!("Successfully converted with {}", converter.name());
-                        return Ok(());
-                    }
-                    Err(e) => {
-                        tracing::warn!("Converter {} failed: {}. Trying next converter...", converter.name(), e);
-                        last_error = Some(e);
-                        // Continue to next converter
-                    }
+            if !converter.is_available() {
+                continue;
+            }
+            any_available = true;
+            if !converter.supports(request.input_format, request.output_format) {
+                continue;
+            }
+            
+            tracing::info!("Trying converter: {}", converter.name());
+            match converter.convert(request, cancel).await {
+                Ok(()) => {
+                    tracing::info!("Successfully converted with {}", converter.name());
+                    return Ok(converter.as_ref());
+                }
+                Err(McpImageError::Cancelled) => {
+                    // Don't fall back to another converter after a cancel
+                    return Err(McpImageError::Cancelled);
+                }
+                Err(e) => {
+                    tracing::warn!("Converter {} failed: {}. Trying next converter...", converter.name(), e);
+                    last_error = Some(e);
+                    // Continue to next converter
                 }
             }
         }
@@ -78,11 +175,46 @@ impl ImageConverter for AutoConverter {
         // If we get here, either no converters were available or all failed
         match last_error {
             Some(e) => Err(e),
-            None => Err(crate::McpImageError::ConverterNotAvailable(
+            None if any_available => Err(McpImageError::ConverterNotAvailable(format!(
+                "No available converter can convert {} to {}",
+                request.input_format, request.output_format
+            ))),
+            None => Err(McpImageError::ConverterNotAvailable(
                 "No image converter available".to_string()
             ))
         }
     }
+}
+
+#[async_trait]
+impl ImageConverter for AutoConverter {
+    async fn convert(&self, request: &ConversionRequest<'_>, cancel: &CancellationToken) -> Result<()> {
+        self.convert_with_fallback(request, cancel).await.map(|_| ())
+    }
+    
+    fn supports(&self, input: ImageFormat, output: ImageFormat) -> bool {
+        self.converters.iter().any(|c| c.supports(input, output))
+    }
+    
+    async fn create_preview(
+        &self,
+        input: &Path,
+        output: &Path,
+        max_dimension: u32,
+        cancel: &CancellationToken,
+    ) -> Result<Preview> {
+        let mut last_error = None;
+        for converter in self.converters.iter().filter(|c| c.is_available()) {
+            match converter.create_preview(input, output, max_dimension, cancel).await {
+                Ok(preview) => return Ok(preview),
+                Err(McpImageError::Cancelled) => return Err(McpImageError::Cancelled),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            McpImageError::ConverterNotAvailable("No image converter available".to_string())
+        }))
+    }
     
     fn is_available(&self) -> bool {
         self.converters.iter().any(|c| c.is_available())
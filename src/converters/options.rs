@@ -0,0 +1,247 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, McpImageError};
+use super::ImageFormat;
+
+/// WebP encoder settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebpOptions {
+    /// Encode losslessly (default) or with lossy compression
+    pub lossless: bool,
+    /// Lossy: visual quality. Lossless: how hard to compress (0-100)
+    #[schemars(range(max = 100))]
+    pub quality: Option<u8>,
+    /// Lossless only: let pixels change slightly for smaller files
+    /// (0 = most change, 100 = off)
+    #[schemars(range(max = 100))]
+    pub near_lossless: Option<u8>,
+    /// Quality of the alpha channel (0-100)
+    #[schemars(range(max = 100))]
+    pub alpha_quality: Option<u8>,
+    /// Encoder effort, 0 (fastest) to 6 (smallest file)
+    #[schemars(range(max = 6))]
+    pub method: u8,
+    /// Lossy only: aim for this output size in bytes
+    #[schemars(range(min = 1))]
+    pub target_size: Option<u64>,
+}
+
+impl Default for WebpOptions {
+    fn default() -> Self {
+        Self {
+            lossless: true,
+            quality: None,
+            near_lossless: None,
+            alpha_quality: None,
+            method: 6,
+            target_size: None,
+        }
+    }
+}
+
+impl WebpOptions {
+    /// Check ranges and combinations the encoder would reject or ignore
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in [
+            ("quality", self.quality),
+            ("near_lossless", self.near_lossless),
+            ("alpha_quality", self.alpha_quality),
+        ] {
+            if value.is_some_and(|v| v > 100) {
+                return Err(McpImageError::InvalidParams(format!("webp.{} must be 0-100", name)));
+            }
+        }
+        if self.method > 6 {
+            return Err(McpImageError::InvalidParams("webp.method must be 0-6".to_string()));
+        }
+        if self.near_lossless.is_some() && !self.lossless {
+            return Err(McpImageError::InvalidParams(
+                "webp.near_lossless needs lossless encoding".to_string()
+            ));
+        }
+        match self.target_size {
+            Some(0) => Err(McpImageError::InvalidParams("webp.target_size must be positive".to_string())),
+            Some(_) if self.lossless => Err(McpImageError::InvalidParams(
+                "webp.target_size needs lossy encoding (lossless: false)".to_string()
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Chroma subsampling of lossy YUV encoders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum ChromaSubsampling {
+    #[serde(rename = "420")]
+    Yuv420,
+    #[serde(rename = "422")]
+    Yuv422,
+    #[serde(rename = "444")]
+    Yuv444,
+}
+
+impl ChromaSubsampling {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Yuv420 => "420",
+            Self::Yuv422 => "422",
+            Self::Yuv444 => "444",
+        }
+    }
+}
+
+/// AVIF encoder settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct AvifOptions {
+    /// Encode losslessly (implies 4:4:4 chroma)
+    pub lossless: bool,
+    /// Visual quality (0-100)
+    #[schemars(range(max = 100))]
+    pub quality: Option<u8>,
+    /// Encoder speed, 0 (slowest, smallest file) to 10 (fastest)
+    #[schemars(range(max = 10))]
+    pub speed: Option<u8>,
+    /// Chroma subsampling: "420", "422" or "444"
+    pub chroma: Option<ChromaSubsampling>,
+    /// Bits per channel: 8, 10 or 12
+    pub bit_depth: Option<u8>,
+}
+
+impl AvifOptions {
+    /// Check ranges and combinations the encoder would reject or ignore
+    pub fn validate(&self) -> Result<()> {
+        if self.quality.is_some_and(|q| q > 100) {
+            return Err(McpImageError::InvalidParams("avif.quality must be 0-100".to_string()));
+        }
+        if self.speed.is_some_and(|s| s > 10) {
+            return Err(McpImageError::InvalidParams("avif.speed must be 0-10".to_string()));
+        }
+        if self.bit_depth.is_some_and(|d| ![8, 10, 12].contains(&d)) {
+            return Err(McpImageError::InvalidParams("avif.bit_depth must be 8, 10 or 12".to_string()));
+        }
+        if self.lossless && self.quality.is_some() {
+            return Err(McpImageError::InvalidParams(
+                "avif.quality does not apply to lossless encoding".to_string()
+            ));
+        }
+        if self.lossless && self.chroma.is_some_and(|c| c != ChromaSubsampling::Yuv444) {
+            return Err(McpImageError::InvalidParams(
+                "avif.lossless needs 444 chroma".to_string()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// JPEG XL encoder settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct JxlOptions {
+    /// Encode losslessly
+    pub lossless: bool,
+    /// Butteraugli distance: 0 is mathematically lossless, 1 visually
+    /// lossless, up to 25. Use either this or `quality`.
+    #[schemars(range(max = 25))]
+    pub distance: Option<f32>,
+    /// Visual quality (0-100), mapped to a distance by the encoder
+    #[schemars(range(max = 100))]
+    pub quality: Option<u8>,
+    /// Encoder effort, 1 (fastest) to 9 (smallest file)
+    #[schemars(range(min = 1, max = 9))]
+    pub effort: Option<u8>,
+    /// Bits per channel: 8, 10, 12 or 16
+    pub bit_depth: Option<u8>,
+}
+
+impl JxlOptions {
+    /// Check ranges and combinations the encoder would reject or ignore
+    pub fn validate(&self) -> Result<()> {
+        if self.distance.is_some_and(|d| !(0.0..=25.0).contains(&d)) {
+            return Err(McpImageError::InvalidParams("jxl.distance must be 0-25".to_string()));
+        }
+        if self.quality.is_some_and(|q| q > 100) {
+            return Err(McpImageError::InvalidParams("jxl.quality must be 0-100".to_string()));
+        }
+        if self.effort.is_some_and(|e| !(1..=9).contains(&e)) {
+            return Err(McpImageError::InvalidParams("jxl.effort must be 1-9".to_string()));
+        }
+        if self.bit_depth.is_some_and(|d| ![8, 10, 12, 16].contains(&d)) {
+            return Err(McpImageError::InvalidParams("jxl.bit_depth must be 8, 10, 12 or 16".to_string()));
+        }
+        if self.distance.is_some() && self.quality.is_some() {
+            return Err(McpImageError::InvalidParams(
+                "jxl.distance and jxl.quality are alternatives; set one".to_string()
+            ));
+        }
+        if self.lossless && (self.distance.is_some() || self.quality.is_some()) {
+            return Err(McpImageError::InvalidParams(
+                "jxl.distance and jxl.quality do not apply to lossless encoding".to_string()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Per-format encoder settings of a conversion. Each is only allowed when
+/// writing its format; formats left unset use the encoder's defaults
+/// (lossless for WebP).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct EncoderOptions {
+    /// WebP encoder settings; lossless when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webp: Option<WebpOptions>,
+    /// AVIF encoder settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avif: Option<AvifOptions>,
+    /// JPEG XL encoder settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jxl: Option<JxlOptions>,
+}
+
+impl EncoderOptions {
+    /// Validate the settings given, as arguments (`InvalidParams`)
+    pub fn validate(&self) -> Result<()> {
+        if let Some(webp) = &self.webp {
+            webp.validate()?;
+        }
+        if let Some(avif) = &self.avif {
+            avif.validate()?;
+        }
+        if let Some(jxl) = &self.jxl {
+            jxl.validate()?;
+        }
+        Ok(())
+    }
+    
+    /// Reject settings for a format other than the one being written
+    pub fn check_format(&self, output: ImageFormat) -> Result<()> {
+        for (name, format, set) in [
+            ("webp", ImageFormat::Webp, self.webp.is_some()),
+            ("avif", ImageFormat::Avif, self.avif.is_some()),
+            ("jxl", ImageFormat::Jxl, self.jxl.is_some()),
+        ] {
+            if set && output != format {
+                return Err(McpImageError::InvalidInput(format!(
+                    "{} options do not apply to {} output",
+                    name, output
+                )));
+            }
+        }
+        Ok(())
+    }
+    
+    pub fn webp(&self) -> WebpOptions {
+        self.webp.unwrap_or_default()
+    }
+    
+    pub fn avif(&self) -> AvifOptions {
+        self.avif.unwrap_or_default()
+    }
+    
+    pub fn jxl(&self) -> JxlOptions {
+        self.jxl.unwrap_or_default()
+    }
+}
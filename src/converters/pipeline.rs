@@ -0,0 +1,286 @@
+use std::path::Path;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, McpImageError};
+use super::{EncoderOptions, Gravity, ImageFormat, Resize};
+
+/// Unsharp-mask sharpening
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sharpen {
+    /// Radius in pixels; 0 derives it from `sigma`
+    pub radius: f64,
+    /// Spread of the blur in pixels (default 1)
+    pub sigma: f64,
+    /// Strength, as a fraction of the difference added back (default 1)
+    pub amount: f64,
+    /// Minimum contrast to sharpen, as a fraction of the range (default 0.05)
+    pub threshold: f64,
+}
+
+impl Default for Sharpen {
+    fn default() -> Self {
+        Self {
+            radius: 0.0,
+            sigma: 1.0,
+            amount: 1.0,
+            threshold: 0.05,
+        }
+    }
+}
+
+/// Colorspace to convert pixels to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Colorspace {
+    Srgb,
+    LinearRgb,
+    Gray,
+    Cmyk,
+    Lab,
+}
+
+/// Convert to an ICC profile, a colorspace, or both (profile first)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ColorConvert {
+    /// Path to an ICC profile to convert to. Images without an embedded
+    /// profile are tagged with it instead.
+    pub profile: Option<String>,
+    pub colorspace: Option<Colorspace>,
+}
+
+/// Overlay another image, such as a logo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Watermark {
+    /// Path to the image to overlay
+    pub image: String,
+    /// Corner or edge to place it at (default southeast)
+    #[serde(default = "default_watermark_gravity")]
+    pub gravity: Gravity,
+    /// Distance from the edges in pixels (default 16)
+    #[serde(default = "default_watermark_margin")]
+    pub margin: u32,
+    /// Opacity in percent (default 50)
+    #[serde(default = "default_watermark_opacity")]
+    #[schemars(range(max = 100))]
+    pub opacity: u8,
+    /// Scale the overlay to this width in pixels first
+    pub width: Option<u32>,
+}
+
+fn default_watermark_gravity() -> Gravity {
+    Gravity::SouthEast
+}
+
+fn default_watermark_margin() -> u32 {
+    16
+}
+
+fn default_watermark_opacity() -> u8 {
+    50
+}
+
+/// Output format and encoder settings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Encode {
+    /// Output format; defaults to the one implied by the output extension
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ImageFormat>,
+    /// Encoder settings for the output format (`webp`, `avif` or `jxl`)
+    #[serde(flatten)]
+    pub options: EncoderOptions,
+}
+
+/// One step of a processing pipeline, chosen by `op`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Step {
+    /// Develop the input with darktable; only as the first step
+    Develop,
+    Resize(Resize),
+    Sharpen(Sharpen),
+    ColorConvert(ColorConvert),
+    Watermark(Watermark),
+    /// Choose the output format and encoder settings; only as the last step
+    Encode(Encode),
+}
+
+impl Step {
+    /// The step's `op` tag
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Develop => "develop",
+            Self::Resize(_) => "resize",
+            Self::Sharpen(_) => "sharpen",
+            Self::ColorConvert(_) => "color_convert",
+            Self::Watermark(_) => "watermark",
+            Self::Encode(_) => "encode",
+        }
+    }
+    
+    /// Check the step's own settings, and that files it reads exist
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Self::Develop => Ok(()),
+            Self::Resize(resize) => resize.validate(),
+            Self::Sharpen(sharpen) => {
+                let values = [sharpen.radius, sharpen.sigma, sharpen.amount, sharpen.threshold];
+                if values.iter().any(|v| !v.is_finite() || *v < 0.0) {
+                    return Err(McpImageError::InvalidParams(
+                        "sharpen settings must be non-negative numbers".to_string()
+                    ));
+                }
+                if sharpen.sigma == 0.0 {
+                    return Err(McpImageError::InvalidParams("sharpen.sigma must be positive".to_string()));
+                }
+                Ok(())
+            }
+            Self::ColorConvert(convert) => {
+                if convert.profile.is_none() && convert.colorspace.is_none() {
+                    return Err(McpImageError::InvalidParams(
+                        "color_convert needs a profile or a colorspace".to_string()
+                    ));
+                }
+                match &convert.profile {
+                    Some(profile) if !Path::new(profile).exists() => Err(McpImageError::FileNotFound(profile.clone())),
+                    _ => Ok(()),
+                }
+            }
+            Self::Watermark(watermark) => {
+                if watermark.opacity > 100 {
+                    return Err(McpImageError::InvalidParams("watermark.opacity must be 0-100".to_string()));
+                }
+                if watermark.width == Some(0) {
+                    return Err(McpImageError::InvalidParams("watermark.width must be positive".to_string()));
+                }
+                if !Path::new(&watermark.image).exists() {
+                    return Err(McpImageError::FileNotFound(watermark.image.clone()));
+                }
+                Ok(())
+            }
+            Self::Encode(encode) => encode.options.validate(),
+        }
+    }
+}
+
+/// A validated pipeline split into the parts different converters run:
+/// an optional darktable develop, then ImageMagick operators and the encode
+#[derive(Debug, Clone, Copy)]
+pub struct Pipeline<'a> {
+    pub develop: bool,
+    /// Steps between the develop and the encode, run in one ImageMagick
+    /// invocation
+    pub operations: &'a [Step],
+    pub encode: Encode,
+}
+
+impl<'a> Pipeline<'a> {
+    /// Validate `steps` and group them by converter
+    pub fn compile(steps: &'a [Step]) -> Result<Self> {
+        if steps.is_empty() {
+            return Err(McpImageError::InvalidParams("a pipeline needs at least one step".to_string()));
+        }
+        for (index, step) in steps.iter().enumerate() {
+            match step {
+                Step::Develop if index != 0 => {
+                    return Err(McpImageError::InvalidParams("develop must be the first step".to_string()));
+                }
+                Step::Encode(_) if index != steps.len() - 1 => {
+                    return Err(McpImageError::InvalidParams("encode must be the last step".to_string()));
+                }
+                _ => step.validate()?,
+            }
+        }
+        
+        let develop = steps.first() == Some(&Step::Develop);
+        let (encode, rest) = match steps.split_last() {
+            Some((Step::Encode(encode), rest)) => (*encode, rest),
+            _ => (Encode::default(), steps),
+        };
+        let operations = if develop { &rest[1..] } else { rest };
+        Ok(Self {
+            develop,
+            operations,
+            encode,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converters::{ResizeMode, WebpOptions};
+    
+    fn resize() -> Step {
+        Step::Resize(Resize {
+            width: Some(800),
+            height: None,
+            mode: ResizeMode::Fit,
+            filter: None,
+            only_shrink: false,
+        })
+    }
+    
+    fn encode_webp() -> Encode {
+        Encode {
+            format: Some(ImageFormat::Webp),
+            options: EncoderOptions {
+                webp: Some(WebpOptions {
+                    lossless: false,
+                    quality: Some(80),
+                    ..WebpOptions::default()
+                }),
+                ..EncoderOptions::default()
+            },
+        }
+    }
+    
+    #[test]
+    fn develop_alone_encodes_by_extension() {
+        let steps = [Step::Develop];
+        
+        let pipeline = Pipeline::compile(&steps).unwrap();
+        
+        assert!(pipeline.develop);
+        assert!(pipeline.operations.is_empty());
+        assert_eq!(pipeline.encode, Encode::default());
+    }
+    
+    #[test]
+    fn develop_and_encode_leave_no_operations() {
+        let steps = [Step::Develop, Step::Encode(encode_webp())];
+        
+        let pipeline = Pipeline::compile(&steps).unwrap();
+        
+        assert!(pipeline.develop);
+        assert!(pipeline.operations.is_empty());
+        assert_eq!(pipeline.encode, encode_webp());
+    }
+    
+    #[test]
+    fn operations_sit_between_develop_and_encode() {
+        let steps = [resize(), Step::Encode(encode_webp())];
+        let pipeline = Pipeline::compile(&steps).unwrap();
+        assert!(!pipeline.develop);
+        assert_eq!(pipeline.operations, [resize()]);
+        assert_eq!(pipeline.encode, encode_webp());
+        
+        let steps = [Step::Develop, resize(), Step::Sharpen(Sharpen::default())];
+        let pipeline = Pipeline::compile(&steps).unwrap();
+        assert!(pipeline.develop);
+        assert_eq!(pipeline.operations, [resize(), Step::Sharpen(Sharpen::default())]);
+        assert_eq!(pipeline.encode, Encode::default());
+    }
+    
+    #[test]
+    fn develop_and_encode_must_bracket_the_pipeline() {
+        let message = |steps: &[Step]| Pipeline::compile(steps).unwrap_err().to_string();
+        
+        assert!(message(&[]).contains("at least one step"));
+        assert!(message(&[resize(), Step::Develop]).contains("develop must be the first step"));
+        assert!(message(&[Step::Encode(encode_webp()), resize()]).contains("encode must be the last step"));
+    }
+}
\ No newline at end of file
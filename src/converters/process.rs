@@ -0,0 +1,93 @@
+use std::path::Path;
+use std::process::{Output, Stdio};
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+
+use crate::{Result, McpImageError};
+
+/// Run a converter command to completion, or until `cancel` fires.
+///
+/// The child is started in its own process group so that cancelling also
+/// terminates any helpers it spawned (delegates, OpenCL workers). When
+/// cancelled, the partially written `output` file is removed and
+/// `McpImageError::Cancelled` is returned.
+pub(crate) async fn run_cancellable(
+    mut command: Command,
+    output: &Path,
+    cancel: &CancellationToken,
+) -> Result<Output> {
+    if cancel.is_cancelled() {
+        return Err(McpImageError::Cancelled);
+    }
+    
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    #[cfg(unix)]
+    command.process_group(0);
+    
+    let child = command.spawn()?;
+    let pid = child.id();
+    
+    tokio::select! {
+        result = child.wait_with_output() => Ok(result?),
+        _ = cancel.cancelled() => {
+            tracing::info!("Cancelling converter process {:?}", pid);
+            kill_process_group(pid);
+            
+            match tokio::fs::remove_file(output).await {
+                Ok(()) => tracing::info!("Removed partial output {}", output.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => tracing::warn!("Failed to remove partial output {}: {}", output.display(), e),
+            }
+            
+            Err(McpImageError::Cancelled)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn kill_process_group(pid: Option<u32>) {
+    if let Some(pid) = pid {
+        // The child is the group leader, so its pid is the group id
+        // SAFETY: kill(2) has no memory-safety preconditions
+        let rc = unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+        if rc != 0 {
+            tracing::warn!(
+                "Failed to kill process group {}: {}",
+                pid,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(_pid: Option<u32>) {
+    // Dropping the child with kill_on_drop terminates it
+}
+
+/// Lines of diagnostic output kept when a converter fails
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Error for a converter process that exited unsuccessfully, carrying the
+/// tail of its stderr (or stdout, where darktable-cli sometimes reports)
+pub(crate) fn converter_failure(converter: &str, output: &Output) -> McpImageError {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let diagnostics = if stderr.trim().is_empty() {
+        String::from_utf8_lossy(&output.stdout)
+    } else {
+        stderr
+    };
+    
+    let lines: Vec<&str> = diagnostics.trim_end().lines().collect();
+    let tail = lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n");
+    
+    McpImageError::ConverterFailed {
+        converter: converter.to_string(),
+        exit_status: output.status.code(),
+        stderr: tail,
+    }
+}
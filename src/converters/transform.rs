@@ -0,0 +1,140 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, McpImageError};
+
+/// How `resize` treats the aspect ratio: `fit` inside the box, `fill` the
+/// box and crop the overflow, or stretch to the `exact` size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeMode {
+    #[default]
+    Fit,
+    Fill,
+    Exact,
+}
+
+/// Resampling filter used when resizing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResizeFilter {
+    Point,
+    Box,
+    Triangle,
+    Catrom,
+    Mitchell,
+    Lanczos,
+    Gaussian,
+}
+
+/// Anchor point of a crop
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Gravity {
+    NorthWest,
+    North,
+    NorthEast,
+    West,
+    Center,
+    East,
+    SouthWest,
+    South,
+    SouthEast,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Resize {
+    /// Target width in pixels; may be left out with `fit`
+    pub width: Option<u32>,
+    /// Target height in pixels; may be left out with `fit`
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub mode: ResizeMode,
+    /// Resampling filter; ImageMagick picks one when omitted
+    pub filter: Option<ResizeFilter>,
+    /// Never enlarge the image; with `fill`, an image smaller than the box
+    /// is trimmed to it but not padded out
+    #[serde(default)]
+    pub only_shrink: bool,
+}
+
+impl Resize {
+    /// Check the sizes suit the mode
+    pub fn validate(&self) -> Result<()> {
+        if self.width == Some(0) || self.height == Some(0) {
+            return Err(McpImageError::InvalidParams("resize width and height must be positive".to_string()));
+        }
+        match (self.mode, self.width, self.height) {
+            (_, None, None) => Err(McpImageError::InvalidParams("resize needs a width or a height".to_string())),
+            (ResizeMode::Fill | ResizeMode::Exact, None, _) | (ResizeMode::Fill | ResizeMode::Exact, _, None) => {
+                Err(McpImageError::InvalidParams(
+                    "resize modes fill and exact need both width and height".to_string()
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A rectangle to keep. Offsets are from the top-left corner, or from the
+/// `gravity` anchor when given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Crop {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub x: i32,
+    #[serde(default)]
+    pub y: i32,
+    pub gravity: Option<Gravity>,
+}
+
+/// Geometry changes applied in a single pass, in this order: auto-orient,
+/// crop, resize, rotate, flip, flop
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct Transform {
+    /// Rotate according to the EXIF orientation tag first (default true)
+    pub auto_orient: bool,
+    pub crop: Option<Crop>,
+    pub resize: Option<Resize>,
+    /// Degrees clockwise
+    pub rotate: Option<f64>,
+    /// Mirror top to bottom
+    pub flip: bool,
+    /// Mirror left to right
+    pub flop: bool,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            auto_orient: true,
+            crop: None,
+            resize: None,
+            rotate: None,
+            flip: false,
+            flop: false,
+        }
+    }
+}
+
+impl Transform {
+    /// Check sizes and combinations ImageMagick would reject or misread
+    pub fn validate(&self) -> Result<()> {
+        if let Some(crop) = &self.crop {
+            if crop.width == 0 || crop.height == 0 {
+                return Err(McpImageError::InvalidParams("crop width and height must be positive".to_string()));
+            }
+        }
+        if let Some(resize) = &self.resize {
+            resize.validate()?;
+        }
+        if self.rotate.is_some_and(|degrees| !degrees.is_finite()) {
+            return Err(McpImageError::InvalidParams("rotate must be a number of degrees".to_string()));
+        }
+        Ok(())
+    }
+}
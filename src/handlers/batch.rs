@@ -0,0 +1,493 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use glob::{MatchOptions, Pattern};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{Result, McpImageError};
+use crate::converters::{EncoderOptions, ImageFormat, Resize, Transform};
+use super::image::{ConvertImageArgs, ConverterChoice, ImageHandler, TransformImageArgs};
+use super::tools::{parse_arguments, schema_for, Tool, ToolAnnotations};
+use super::{ProgressReporter, RequestContext};
+
+/// Most files converted at once by one batch
+const MAX_PARALLEL: usize = 64;
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct BatchConvertArgs {
+    /// Directory of images, or a glob pattern such as `/shoot/**/*.dng`
+    pub input: String,
+    /// Directory to write the outputs to
+    pub output_dir: String,
+    /// Output file name. `{stem}` and `{ext}` are the input's name without
+    /// and its extension; `{box_width}` and `{box_height}` the `resize`
+    /// box, which is not the output size with `fit` or `only_shrink`. The
+    /// extension picks the format unless `format` is given.
+    #[serde(default = "default_filename_template")]
+    pub filename_template: String,
+    /// Output format; defaults to the one implied by the template extension
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ImageFormat>,
+    /// Include subdirectories of a directory `input`, recreating them under
+    /// `output_dir` (globs use `**` instead)
+    #[serde(default)]
+    pub recursive: bool,
+    /// Leave files alone whose output is at least as new as the input
+    #[serde(default)]
+    pub skip_up_to_date: bool,
+    /// Files converted at the same time (default 4); the server-wide
+    /// conversion limit still applies
+    #[serde(default = "default_max_parallel")]
+    #[schemars(range(min = 1, max = 64))]
+    pub max_parallel: usize,
+    /// Which converter to use (auto selects the best available). With
+    /// `resize`, darktable develops and ImageMagick resizes.
+    #[serde(default)]
+    pub converter: ConverterChoice,
+    /// Resize every image, after applying its EXIF orientation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resize: Option<Resize>,
+    /// Encoder settings for the output format (`webp`, `avif` or `jxl`)
+    #[serde(flatten)]
+    pub options: EncoderOptions,
+}
+
+fn default_filename_template() -> String {
+    "{stem}.webp".to_string()
+}
+
+fn default_max_parallel() -> usize {
+    4
+}
+
+/// What happened to one input file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchStatus {
+    Converted,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BatchFileReport {
+    pub input_path: String,
+    pub output_path: String,
+    pub status: BatchStatus,
+    /// Why the file failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BatchResult {
+    pub total: usize,
+    pub converted: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    /// One entry per input file, in path order
+    pub files: Vec<BatchFileReport>,
+    pub duration_ms: u64,
+}
+
+/// An input file and where its output goes
+struct BatchJob {
+    input: PathBuf,
+    output: PathBuf,
+    /// Reason to fail the file without converting it
+    conflict: Option<String>,
+}
+
+/// Expand `{stem}`, `{ext}`, `{box_width}` and `{box_height}` in a filename template
+fn render_template(template: &str, stem: &str, ext: &str, resize: Option<&Resize>) -> Result<String> {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..].find('}').ok_or_else(|| {
+            McpImageError::InvalidParams(format!("unclosed {{ in filename_template {:?}", template))
+        })?;
+        let value = match &rest[start + 1..start + end] {
+            "stem" => stem.to_string(),
+            "ext" => ext.to_string(),
+            "box_width" => resize.and_then(|r| r.width).map(|w| w.to_string()).ok_or_else(|| {
+                McpImageError::InvalidParams("{box_width} in filename_template needs resize.width".to_string())
+            })?,
+            "box_height" => resize.and_then(|r| r.height).map(|h| h.to_string()).ok_or_else(|| {
+                McpImageError::InvalidParams("{box_height} in filename_template needs resize.height".to_string())
+            })?,
+            // Output sizes are only known after converting
+            name @ ("width" | "height") => {
+                return Err(McpImageError::InvalidParams(format!(
+                    "unknown placeholder {{{0}}} in filename_template; {{box_{0}}} is the resize box",
+                    name
+                )));
+            }
+            other => {
+                return Err(McpImageError::InvalidParams(format!(
+                    "unknown placeholder {{{}}} in filename_template",
+                    other
+                )));
+            }
+        };
+        rendered.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// The directory a glob pattern is rooted at: its components up to the
+/// first one with a wildcard
+fn glob_base(pattern: &str) -> PathBuf {
+    let mut base = PathBuf::new();
+    let components: Vec<Component> = Path::new(pattern).components().collect();
+    for (index, component) in components.iter().enumerate() {
+        let literal = !component.as_os_str().to_string_lossy().contains(['*', '?', '[']);
+        if !literal || index == components.len() - 1 {
+            break;
+        }
+        base.push(component);
+    }
+    base
+}
+
+/// Image files named by `input` (a directory or a glob), in path order, with
+/// the directory their relative paths start from
+fn find_inputs(input: &str, recursive: bool) -> Result<(PathBuf, Vec<PathBuf>)> {
+    let (base, pattern) = if Path::new(input).is_dir() {
+        let base = PathBuf::from(input);
+        let escaped = Pattern::escape(input);
+        let pattern = if recursive {
+            format!("{}/**/*", escaped.trim_end_matches('/'))
+        } else {
+            format!("{}/*", escaped.trim_end_matches('/'))
+        };
+        (base, pattern)
+    } else if recursive {
+        return Err(McpImageError::InvalidParams(
+            "recursive applies to a directory input; use ** in a glob".to_string()
+        ));
+    } else {
+        (glob_base(input), input.to_string())
+    };
+    
+    let options = MatchOptions {
+        require_literal_leading_dot: true,
+        ..MatchOptions::new()
+    };
+    let entries = glob::glob_with(&pattern, options)
+        .map_err(|e| McpImageError::InvalidParams(format!("bad glob {:?}: {}", input, e)))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| match entry {
+            Ok(path) => Some(path),
+            Err(e) => {
+                tracing::warn!("Skipping unreadable path: {}", e);
+                None
+            }
+        })
+        .filter(|path| path.is_file() && ImageFormat::from_path(path).is_some())
+        .collect();
+    files.sort();
+    Ok((base, files))
+}
+
+/// Pair every input matched by `input` with its output under
+/// `output_dir`, marking outputs that would overwrite an input or another
+/// output. Blocking: walks the filesystem and resolves each path.
+fn plan_jobs(
+    input: &str,
+    recursive: bool,
+    output_dir: &Path,
+    template: &str,
+    resize: Option<&Resize>,
+) -> Result<Vec<BatchJob>> {
+    let (base, inputs) = find_inputs(input, recursive)?;
+    let mut planned = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let ext = input.extension().unwrap_or_default().to_string_lossy();
+        let name = render_template(template, &stem, &ext, resize)?;
+        let relative = input
+            .parent()
+            .and_then(|parent| parent.strip_prefix(&base).ok())
+            .unwrap_or(Path::new(""));
+        let output = output_dir.join(relative).join(name);
+        planned.push((file_key(&input), file_key(&output), input, output));
+    }
+    
+    // When writing next to the inputs, files an earlier run produced
+    // match as well; they are this batch's outputs, not more inputs
+    let outputs: HashSet<PathBuf> = planned
+        .iter()
+        .filter(|(input_key, output_key, ..)| input_key != output_key)
+        .map(|(_, output_key, ..)| output_key.clone())
+        .collect();
+    planned.retain(|(input_key, ..)| !outputs.contains(input_key));
+    if planned.is_empty() {
+        return Err(McpImageError::InvalidInput(format!("No images found for {}", input)));
+    }
+    
+    let mut claimed: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut jobs = Vec::with_capacity(planned.len());
+    for (input_key, output_key, input, output) in planned {
+        let conflict = if output_key == input_key {
+            Some("output would overwrite the input".to_string())
+        } else if let Some(other) = claimed.get(&output_key) {
+            Some(format!("output is also the output of {}", other.display()))
+        } else {
+            claimed.insert(output_key, input.clone());
+            None
+        };
+        jobs.push(BatchJob { input, output, conflict });
+    }
+    
+    Ok(jobs)
+}
+
+/// `path` with its directory resolved, so two spellings of the same file
+/// compare equal even before the file exists
+fn file_key(path: &Path) -> PathBuf {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    match (dir.canonicalize(), path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Whether `output` exists and was modified no earlier than `input`
+fn is_up_to_date(input: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(input), modified(output)) {
+        (Some(input), Some(output)) => output >= input,
+        _ => false,
+    }
+}
+
+impl ImageHandler {
+    /// Convert every image matched by `args.input` into `args.output_dir`,
+    /// reporting each file instead of stopping at the first failure
+    pub async fn batch_convert(&self, args: BatchConvertArgs, ctx: &RequestContext) -> Result<BatchResult> {
+        let started = Instant::now();
+        args.options.validate()?;
+        if let Some(resize) = &args.resize {
+            resize.validate()?;
+        }
+        if !(1..=MAX_PARALLEL).contains(&args.max_parallel) {
+            return Err(McpImageError::InvalidParams(format!("max_parallel must be 1-{}", MAX_PARALLEL)));
+        }
+        if args.filename_template.contains(['/', '\\']) {
+            return Err(McpImageError::InvalidParams(
+                "filename_template must be a file name, not a path".to_string()
+            ));
+        }
+        let sample = render_template(&args.filename_template, "image", "dng", args.resize.as_ref())?;
+        let output_format = Self::output_format(Path::new(&sample), args.format)?;
+        args.options.check_format(output_format)?;
+        
+        // Walking a large tree and resolving every path blocks, so it is
+        // kept off the async workers
+        let jobs = {
+            let input = args.input.clone();
+            let output_dir = PathBuf::from(&args.output_dir);
+            let template = args.filename_template.clone();
+            let (recursive, resize) = (args.recursive, args.resize);
+            tokio::task::spawn_blocking(move || {
+                plan_jobs(&input, recursive, &output_dir, &template, resize.as_ref())
+            })
+            .await
+            .map_err(|e| McpImageError::Mcp(format!("Listing batch inputs failed: {}", e)))??
+        };
+        
+        let total = jobs.len();
+        let done = AtomicUsize::new(0);
+        ctx.progress.report(0, Some(total as u64), format!("Converting {} files", total));
+        let files: Vec<BatchFileReport> = stream::iter(jobs)
+            .map(|job| {
+                let done = &done;
+                let args = &args;
+                async move {
+                    let report = self.batch_file(job, args, output_format, ctx).await;
+                    let done = done.fetch_add(1, Ordering::SeqCst) + 1;
+                    ctx.progress.report(
+                        done as u64,
+                        Some(total as u64),
+                        format!("{} of {} files done", done, total),
+                    );
+                    report
+                }
+            })
+            .buffered(args.max_parallel)
+            .collect()
+            .await;
+        if ctx.cancel.is_cancelled() {
+            return Err(McpImageError::Cancelled);
+        }
+        
+        let count = |status| files.iter().filter(|f| f.status == status).count();
+        Ok(BatchResult {
+            total,
+            converted: count(BatchStatus::Converted),
+            skipped: count(BatchStatus::Skipped),
+            failed: count(BatchStatus::Failed),
+            files,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+    
+    /// Convert one file of a batch, or skip it when its output is current
+    async fn batch_file(
+        &self,
+        job: BatchJob,
+        args: &BatchConvertArgs,
+        output_format: ImageFormat,
+        ctx: &RequestContext,
+    ) -> BatchFileReport {
+        let started = Instant::now();
+        let mut report = BatchFileReport {
+            input_path: job.input.display().to_string(),
+            output_path: job.output.display().to_string(),
+            status: BatchStatus::Failed,
+            error: job.conflict,
+            output_bytes: None,
+            width: None,
+            height: None,
+            duration_ms: 0,
+        };
+        if report.error.is_some() {
+            return report;
+        }
+        if args.skip_up_to_date && is_up_to_date(&job.input, &job.output) {
+            report.status = BatchStatus::Skipped;
+            return report;
+        }
+        
+        // Progress is reported per batch, not per file
+        let file_ctx = RequestContext::new(ctx.cancel.clone(), ProgressReporter::disabled());
+        let result = match args.resize {
+            Some(resize) => {
+                let transform_args = TransformImageArgs {
+                    input_path: report.input_path.clone(),
+                    output_path: report.output_path.clone(),
+                    format: Some(output_format),
+                    develop: args.converter == ConverterChoice::Darktable,
+                    transform: Transform {
+                        resize: Some(resize),
+                        ..Transform::default()
+                    },
+                    options: args.options,
+                    return_preview: false,
+                };
+                self.transform_image(transform_args, &file_ctx).await
+            }
+            None => {
+                let convert_args = ConvertImageArgs {
+                    input_path: report.input_path.clone(),
+                    output_path: report.output_path.clone(),
+                    format: Some(output_format),
+                    converter: args.converter,
+                    options: args.options,
+                    return_preview: false,
+                };
+                self.convert_image(convert_args, &file_ctx).await
+            }
+        };
+        
+        match result {
+            Ok(result) => {
+                report.status = BatchStatus::Converted;
+                report.output_bytes = Some(result.output_bytes);
+                report.width = result.width;
+                report.height = result.height;
+            }
+            Err(e) => {
+                tracing::warn!("Batch conversion of {} failed: {}", report.input_path, e);
+                report.error = Some(e.to_string());
+            }
+        }
+        report.duration_ms = started.elapsed().as_millis() as u64;
+        report
+    }
+}
+
+pub struct BatchConvertTool {
+    handler: Arc<ImageHandler>,
+}
+
+impl BatchConvertTool {
+    pub fn new(handler: Arc<ImageHandler>) -> Self {
+        Self { handler }
+    }
+}
+
+#[async_trait]
+impl Tool for BatchConvertTool {
+    fn name(&self) -> &'static str {
+        "batch_convert"
+    }
+    
+    fn title(&self) -> &'static str {
+        "Batch convert images"
+    }
+    
+    fn description(&self) -> &'static str {
+        "Convert every image in a directory or matching a glob into an output directory, naming outputs \
+         from a template such as {stem}_{box_width}.webp, and report each file's outcome"
+    }
+    
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::WRITES_OUTPUT
+    }
+    
+    fn input_schema(&self) -> Value {
+        schema_for::<BatchConvertArgs>()
+    }
+    
+    fn output_schema(&self) -> Option<Value> {
+        Some(schema_for::<BatchResult>())
+    }
+    
+    fn is_available(&self) -> bool {
+        self.handler.has_converter()
+    }
+    
+    async fn call(&self, arguments: Value, ctx: &RequestContext) -> Result<Value> {
+        let args: BatchConvertArgs = parse_arguments(arguments)?;
+        let result = self.handler.batch_convert(args, ctx).await?;
+        
+        let mut text = format!(
+            "Converted {} of {} files ({} skipped as up to date, {} failed)",
+            result.converted, result.total, result.skipped, result.failed
+        );
+        for file in result.files.iter().filter(|f| f.status == BatchStatus::Failed) {
+            text.push_str(&format!(
+                "\n- {}: {}",
+                file.input_path,
+                file.error.as_deref().unwrap_or("failed")
+            ));
+        }
+        Ok(json!({
+            "content": [{
+                "type": "text",
+                "text": text
+            }],
+            "structuredContent": serde_json::to_value(&result)?
+        }))
+    }
+}
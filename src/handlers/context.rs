@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use serde_json::{json, Value};
+use tokio_util::sync::CancellationToken;
+
+use crate::transport::Outbox;
+
+/// Per-request state handed to tool handlers
+#[derive(Clone, Debug, Default)]
+pub struct RequestContext {
+    /// Fires when the client cancels the request
+    pub cancel: CancellationToken,
+    /// Sink for `notifications/progress` tied to the request's progress token
+    pub progress: ProgressReporter,
+}
+
+impl RequestContext {
+    pub fn new(cancel: CancellationToken, progress: ProgressReporter) -> Self {
+        Self { cancel, progress }
+    }
+}
+
+/// Emits `notifications/progress` for a request that carried
+/// `_meta.progressToken`. Reporting is a no-op when the client did not ask
+/// for progress.
+#[derive(Clone, Debug, Default)]
+pub struct ProgressReporter {
+    target: Option<(Value, Outbox)>,
+    /// Last reported progress plus one (0 = nothing reported yet), used to
+    /// keep progress strictly increasing across concurrent reporters
+    last: Arc<AtomicU64>,
+}
+
+impl ProgressReporter {
+    pub fn new(token: Option<Value>, outbox: Outbox) -> Self {
+        Self {
+            target: token.map(|token| (token, outbox)),
+            last: Arc::new(AtomicU64::new(0)),
+        }
+    }
+    
+    /// A reporter that discards every update
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+    
+    /// Whether the client asked for progress on this request
+    pub fn is_enabled(&self) -> bool {
+        self.target.is_some()
+    }
+    
+    /// Report `progress` out of `total` units (files for batch jobs, phases
+    /// for single conversions). Updates that don't move forward are dropped,
+    /// since the spec requires progress to increase with every notification.
+    pub fn report(&self, progress: u64, total: Option<u64>, message: impl Into<String>) {
+        let Some((token, outbox)) = &self.target else {
+            return;
+        };
+        
+        let previous = self.last.fetch_max(progress + 1, Ordering::SeqCst);
+        if previous > progress {
+            return;
+        }
+        
+        let mut params = json!({
+            "progressToken": token,
+            "progress": progress,
+            "message": message.into()
+        });
+        if let Some(total) = total {
+            params["total"] = json!(total);
+        }
+        
+        outbox.notify("notifications/progress", params);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn progress_only_moves_forward() {
+        let (outbox, mut sent) = Outbox::channel();
+        let progress = ProgressReporter::new(Some(json!(7)), outbox);
+        let copy = progress.clone();
+        
+        for (reporter, step) in [(&progress, 0), (&copy, 2), (&progress, 1), (&copy, 2), (&progress, 3)] {
+            reporter.report(step, Some(3), "step");
+        }
+        
+        let mut reported = Vec::new();
+        while let Ok(message) = sent.try_recv() {
+            assert_eq!(message["params"]["progressToken"], 7);
+            reported.push(message["params"]["progress"].as_u64().unwrap());
+        }
+        assert_eq!(reported, [0, 2, 3]);
+    }
+    
+    #[test]
+    fn progress_without_a_token_is_discarded() {
+        let (outbox, mut sent) = Outbox::channel();
+        ProgressReporter::new(None, outbox).report(1, None, "step");
+        assert!(sent.try_recv().is_err());
+    }
+}
@@ -1,222 +1,1007 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use async_trait::async_trait;
+use base64::Engine;
+use schemars::JsonSchema;
+use tokio::sync::{Semaphore, SemaphorePermit};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 use crate::{Result, McpImageError};
-use crate::converters::{AutoConverter, ImageConverter, ImageMagickConverter, DarktableConverter};
+use crate::converters::{
+    AutoConverter, ConversionRequest, DarktableConverter, EncoderOptions, ImageConverter, ImageFormat,
+    ImageMagickConverter, Pipeline, Preview, Step, Transform, WebpOptions,
+};
+use super::batch::BatchConvertTool;
+use super::{probe, OutputLog, RequestContext};
+use super::tools::{parse_arguments, schema_for, Tool, ToolAnnotations, ToolRegistry};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Progress phases reported for a single conversion
+const CONVERSION_PHASES: u64 = 3;
+
+/// Longest edge of previews returned with `return_preview`
+const PREVIEW_MAX_DIMENSION: u32 = 512;
+
+/// Previews larger than this are left out of the tool result
+const PREVIEW_MAX_BYTES: usize = 512 * 1024;
+
+/// Which converter to use; `Auto` tries each available one in turn
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConverterChoice {
+    #[default]
+    Auto,
+    Imagemagick,
+    Darktable,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ConvertDngToWebpArgs {
+    /// Path to DNG file
     pub input_path: String,
+    /// Path for WebP output
     pub output_path: String,
-    #[serde(default = "default_converter")]
-    pub converter: String,
+    /// Which converter to use (auto selects the best available)
+    #[serde(default)]
+    pub converter: ConverterChoice,
+    /// WebP encoder settings; lossless when omitted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webp: Option<WebpOptions>,
+    /// Include a downscaled preview of the output as an image content block
+    #[serde(default)]
+    pub return_preview: bool,
 }
 
-fn default_converter() -> String {
-    "auto".to_string()
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ConvertImageArgs {
+    /// Path to the image to convert
+    pub input_path: String,
+    /// Path for the converted image
+    pub output_path: String,
+    /// Output format; defaults to the one implied by the output extension
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ImageFormat>,
+    /// Which converter to use (auto selects the best available)
+    #[serde(default)]
+    pub converter: ConverterChoice,
+    /// Encoder settings for the output format (`webp`, `avif` or `jxl`)
+    #[serde(flatten)]
+    pub options: EncoderOptions,
+    /// Include a downscaled preview of the output as an image content block
+    #[serde(default)]
+    pub return_preview: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct TransformImageArgs {
+    /// Path to the image to transform
+    pub input_path: String,
+    /// Path for the transformed image
+    pub output_path: String,
+    /// Output format; defaults to the one implied by the output extension
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ImageFormat>,
+    /// Develop the input with darktable first, e.g. to use its RAW
+    /// processing instead of ImageMagick's
+    #[serde(default)]
+    pub develop: bool,
+    /// Geometry changes (`auto_orient`, `crop`, `resize`, `rotate`, `flip`,
+    /// `flop`)
+    #[serde(flatten)]
+    pub transform: Transform,
+    /// Encoder settings for the output format (`webp`, `avif` or `jxl`)
+    #[serde(flatten)]
+    pub options: EncoderOptions,
+    /// Include a downscaled preview of the output as an image content block
+    #[serde(default)]
+    pub return_preview: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ProcessPipelineArgs {
+    /// Path to the image to process
+    pub input_path: String,
+    /// Path for the result
+    pub output_path: String,
+    /// Steps to apply in order: `develop` (first only), `resize`,
+    /// `sharpen`, `color_convert`, `watermark`, `encode` (last only)
+    pub steps: Vec<Step>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CheckConvertersArgs {}
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct ConversionResult {
     pub success: bool,
     pub message: String,
+    /// Path of the written file
     pub output_path: String,
+    /// Converter that produced the output
+    pub converter: String,
+    /// Format of the written file
+    pub format: ImageFormat,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    /// Output dimensions, when known from the converter or the format's
+    /// header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Time from queueing to completion
+    pub duration_ms: u64,
+    /// Preview requested with `return_preview`
+    #[serde(skip)]
+    pub preview: Option<Preview>,
+    /// Why a requested preview could not be returned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview_error: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct StepTiming {
+    /// The step's `op`; `encode` is listed even when not given
+    pub op: String,
+    /// Converter that ran the step
+    pub converter: String,
+    /// Which converter invocation ran the step, from 1
+    pub invocation: usize,
+    /// Duration of that invocation, shared by all steps it ran
+    pub duration_ms: u64,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PipelineResult {
+    pub success: bool,
+    pub message: String,
+    /// Path of the written file
+    pub output_path: String,
+    /// Format of the written file
+    pub format: ImageFormat,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    /// Output dimensions, when known from the converter or the format's
+    /// header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Converter runs the steps were compiled into
+    pub invocations: usize,
+    pub steps: Vec<StepTiming>,
+    /// Time from queueing to completion
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct ConverterInfo {
     pub name: String,
     pub available: bool,
+    /// Formats the installed build cannot handle for lack of a delegate
+    /// library (ImageMagick only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub missing_delegates: Vec<ImageFormat>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct CheckConvertersResult {
     pub converters: Vec<ConverterInfo>,
     pub available_count: usize,
 }
 
+/// Geometry changes applied by ImageMagick while converting
+#[derive(Debug, Clone, Copy)]
+struct TransformStep {
+    transform: Transform,
+    /// Develop the input with darktable before ImageMagick reads it
+    develop: bool,
+}
+
 pub struct ImageHandler {
     auto_converter: AutoConverter,
     imagemagick: ImageMagickConverter,
     darktable: DarktableConverter,
+    conversion_slots: Arc<Semaphore>,
+    outputs: Arc<OutputLog>,
+}
+
+impl Default for ImageHandler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ImageHandler {
     pub fn new() -> Self {
+        Self::with_concurrency_limit(crate::ServerConfig::default().max_concurrent_conversions)
+    }
+    
+    /// Create a handler that runs at most `limit` conversions at a time
+    pub fn with_concurrency_limit(limit: usize) -> Self {
         Self {
             auto_converter: AutoConverter::new(),
             imagemagick: ImageMagickConverter::new(),
             darktable: DarktableConverter::new(),
+            conversion_slots: Arc::new(Semaphore::new(limit.max(1))),
+            outputs: Arc::new(OutputLog::default()),
         }
     }
     
-    pub async fn convert_dng_to_webp(&self, args: ConvertDngToWebpArgs) -> Result<Value> {
+    /// Whether any converter is installed
+    pub fn has_converter(&self) -> bool {
+        !self.auto_converter.available_converters().is_empty()
+    }
+    
+    /// Outputs written by this handler, shared with the resource handler
+    pub fn output_log(&self) -> Arc<OutputLog> {
+        self.outputs.clone()
+    }
+    
+    pub async fn convert_dng_to_webp(
+        &self,
+        args: ConvertDngToWebpArgs,
+        ctx: &RequestContext,
+    ) -> Result<ConversionResult> {
+        if ImageFormat::from_path(Path::new(&args.input_path)) != Some(ImageFormat::Dng) {
+            return Err(McpImageError::InvalidInput(
+                "Input file must be a DNG file".to_string()
+            ));
+        }
+        
+        let args = ConvertImageArgs {
+            input_path: args.input_path,
+            output_path: args.output_path,
+            format: Some(ImageFormat::Webp),
+            converter: args.converter,
+            options: EncoderOptions {
+                webp: args.webp,
+                ..EncoderOptions::default()
+            },
+            return_preview: args.return_preview,
+        };
+        self.convert_image(args, ctx).await
+    }
+    
+    pub async fn convert_image(
+        &self,
+        args: ConvertImageArgs,
+        ctx: &RequestContext,
+    ) -> Result<ConversionResult> {
+        self.run_conversion(args, None, ctx).await
+    }
+    
+    pub async fn transform_image(
+        &self,
+        args: TransformImageArgs,
+        ctx: &RequestContext,
+    ) -> Result<ConversionResult> {
+        args.transform.validate()?;
+        if args.develop && !self.darktable.is_available() {
+            return Err(McpImageError::ConverterNotAvailable(
+                "darktable-cli is not available".to_string()
+            ));
+        }
+        if args.develop {
+            let input_format = ImageFormat::from_path(Path::new(&args.input_path));
+            if !input_format.is_some_and(|format| self.darktable.supports(format, ImageFormat::Tiff)) {
+                return Err(McpImageError::InvalidInput(format!(
+                    "darktable cannot develop {}",
+                    args.input_path
+                )));
+            }
+        }
+        
+        let step = TransformStep {
+            transform: args.transform,
+            develop: args.develop,
+        };
+        let args = ConvertImageArgs {
+            input_path: args.input_path,
+            output_path: args.output_path,
+            format: args.format,
+            converter: ConverterChoice::Imagemagick,
+            options: args.options,
+            return_preview: args.return_preview,
+        };
+        self.run_conversion(args, Some(step), ctx).await
+    }
+    
+    /// Convert `args.input_path`, applying `step` on the way if given
+    async fn run_conversion(
+        &self,
+        args: ConvertImageArgs,
+        step: Option<TransformStep>,
+        ctx: &RequestContext,
+    ) -> Result<ConversionResult> {
+        let started = Instant::now();
         let input_path = PathBuf::from(&args.input_path);
         let output_path = PathBuf::from(&args.output_path);
+        let phases = if args.return_preview { CONVERSION_PHASES + 1 } else { CONVERSION_PHASES };
+        args.options.validate()?;
         
-        // Select converter based on user preference
-        let converter: &dyn ImageConverter = match args.converter.as_str() {
-            "imagemagick" => {
+        if !input_path.exists() {
+            return Err(McpImageError::FileNotFound(args.input_path));
+        }
+        let input_format = ImageFormat::from_path(&input_path).ok_or_else(|| {
+            McpImageError::InvalidInput(format!("Unsupported input format: {}", args.input_path))
+        })?;
+        let output_format = Self::output_format(&output_path, args.format)?;
+        args.options.check_format(output_format)?;
+        let request = ConversionRequest {
+            input: &input_path,
+            input_format,
+            output: &output_path,
+            output_format,
+            options: args.options,
+        };
+        
+        // Select converter based on user preference; None lets the auto
+        // converter fall back through every available one
+        let requested: Option<&dyn ImageConverter> = match args.converter {
+            ConverterChoice::Imagemagick => {
                 if !self.imagemagick.is_available() {
                     return Err(McpImageError::ConverterNotAvailable(
                         "ImageMagick is not available".to_string()
                     ));
                 }
-                &self.imagemagick
+                Some(&self.imagemagick)
             },
-            "darktable" => {
+            ConverterChoice::Darktable => {
                 if !self.darktable.is_available() {
                     return Err(McpImageError::ConverterNotAvailable(
                         "darktable-cli is not available".to_string()
                     ));
                 }
-                &self.darktable
+                Some(&self.darktable)
             },
-            _ => &self.auto_converter,
+            ConverterChoice::Auto => None,
         };
+        if let Some(converter) = requested {
+            if !converter.supports(input_format, output_format) {
+                return Err(McpImageError::InvalidInput(format!(
+                    "{} cannot convert {} to {}",
+                    converter.name(),
+                    input_format,
+                    output_format
+                )));
+            }
+        }
         
-        // Perform conversion
-        converter.convert_dng_to_webp(&input_path, &output_path).await?;
+        // Wait for a free conversion slot, then perform conversion
+        ctx.progress.report(0, Some(phases), "Waiting for a conversion slot");
+        let _permit = self.conversion_slot(ctx).await?;
         
-        let result = ConversionResult {
+        let converter_label = match step {
+            Some(TransformStep { develop: true, .. }) => "darktable+imagemagick",
+            _ => requested.map_or("auto", |c| c.name()),
+        };
+        ctx.progress.report(1, Some(phases), format!("Decoding and encoding with {}", converter_label));
+        let (converter, reported_dimensions) = match (step, requested) {
+            (Some(step), _) => {
+                let dimensions = self.apply_transform(&request, &step, ctx).await?;
+                (&self.imagemagick as &dyn ImageConverter, dimensions)
+            }
+            (None, Some(converter)) => {
+                converter.convert(&request, &ctx.cancel).await?;
+                (converter, None)
+            }
+            (None, None) => {
+                let converter = self.auto_converter
+                    .convert_with_fallback(&request, &ctx.cancel)
+                    .await?;
+                (converter, None)
+            }
+        };
+        let converter_label = if step.is_some() { converter_label } else { converter.name() };
+        
+        ctx.progress.report(2, Some(phases), "Verifying output");
+        let output_bytes = Self::verify_output(&output_path).await?;
+        self.outputs.record(&output_path);
+        let input_bytes = tokio::fs::metadata(&input_path).await.map(|m| m.len()).unwrap_or(0);
+        let dimensions = match reported_dimensions {
+            Some(dimensions) => Some(dimensions),
+            None => probe::image_dimensions(&output_path).await,
+        };
+        
+        let (preview, preview_error) = if args.return_preview {
+            ctx.progress.report(3, Some(phases), "Rendering preview");
+            match Self::render_preview(converter, &input_path, &output_path, ctx).await {
+                Ok(preview) => (Some(preview), None),
+                Err(McpImageError::Cancelled) => return Err(McpImageError::Cancelled),
+                Err(e) => {
+                    tracing::warn!("No preview for {}: {}", args.output_path, e);
+                    (None, Some(e.to_string()))
+                }
+            }
+        } else {
+            (None, None)
+        };
+        ctx.progress.report(phases, Some(phases), "Conversion complete");
+        
+        Ok(ConversionResult {
+            success: true,
+            message: format!(
+                "Successfully {} {} to {} using {}",
+                if step.is_some() { "transformed" } else { "converted" },
+                args.input_path,
+                args.output_path,
+                converter_label
+            ),
+            output_path: args.output_path,
+            converter: converter_label.to_string(),
+            format: output_format,
+            input_bytes,
+            output_bytes,
+            width: dimensions.map(|(w, _)| w),
+            height: dimensions.map(|(_, h)| h),
+            duration_ms: started.elapsed().as_millis() as u64,
+            preview,
+            preview_error,
+        })
+    }
+    
+    /// Run `args.steps` in as few converter invocations as possible:
+    /// darktable for a leading `develop`, then one ImageMagick run for the
+    /// rest. darktable writes the output itself when nothing is left for
+    /// ImageMagick and it can encode the format.
+    pub async fn process_pipeline(
+        &self,
+        args: ProcessPipelineArgs,
+        ctx: &RequestContext,
+    ) -> Result<PipelineResult> {
+        let started = Instant::now();
+        let input_path = PathBuf::from(&args.input_path);
+        let output_path = PathBuf::from(&args.output_path);
+        let pipeline = Pipeline::compile(&args.steps)?;
+        
+        if !input_path.exists() {
+            return Err(McpImageError::FileNotFound(args.input_path));
+        }
+        let input_format = ImageFormat::from_path(&input_path).ok_or_else(|| {
+            McpImageError::InvalidInput(format!("Unsupported input format: {}", args.input_path))
+        })?;
+        let output_format = Self::output_format(&output_path, pipeline.encode.format)?;
+        let options = pipeline.encode.options;
+        options.check_format(output_format)?;
+        
+        if pipeline.develop {
+            if !self.darktable.is_available() {
+                return Err(McpImageError::ConverterNotAvailable(
+                    "darktable-cli is not available".to_string()
+                ));
+            }
+            if !self.darktable.supports(input_format, ImageFormat::Tiff) {
+                return Err(McpImageError::InvalidInput(format!(
+                    "darktable cannot develop {}",
+                    args.input_path
+                )));
+            }
+        }
+        let darktable_only = pipeline.develop
+            && pipeline.operations.is_empty()
+            && ImageFormat::from_path(&output_path) == Some(output_format)
+            && self.darktable.can_export(output_format, &options);
+        if !darktable_only && !self.imagemagick.is_available() {
+            return Err(McpImageError::ConverterNotAvailable(
+                "ImageMagick is not available".to_string()
+            ));
+        }
+        let invocations = if pipeline.develop && !darktable_only { 2 } else { 1 };
+        let phases = invocations as u64 + 2;
+        
+        ctx.progress.report(0, Some(phases), "Waiting for a conversion slot");
+        let _permit = self.conversion_slot(ctx).await?;
+        
+        let request = ConversionRequest {
+            input: &input_path,
+            input_format,
+            output: &output_path,
+            output_format,
+            options,
+        };
+        // Intermediates live here and are removed when it drops, whether
+        // or not a step fails
+        let intermediates = tempfile::tempdir()?;
+        let developed = intermediates.path().join("developed.tif");
+        let mut steps = Vec::new();
+        let mut record_timing = |ops: &[&str], converter: &str, elapsed: std::time::Duration| {
+            let invocation = steps.last().map_or(1, |s: &StepTiming| s.invocation + 1);
+            for op in ops {
+                steps.push(StepTiming {
+                    op: op.to_string(),
+                    converter: converter.to_string(),
+                    invocation,
+                    duration_ms: elapsed.as_millis() as u64,
+                });
+            }
+        };
+        
+        let mut dimensions = None;
+        if darktable_only {
+            ctx.progress.report(1, Some(phases), "Developing and encoding with darktable");
+            let step_started = Instant::now();
+            self.darktable.convert(&request, &ctx.cancel).await?;
+            record_timing(&["develop", "encode"], self.darktable.name(), step_started.elapsed());
+        } else {
+            let mut source = request.clone();
+            if pipeline.develop {
+                ctx.progress.report(1, Some(phases), "Developing with darktable");
+                let step_started = Instant::now();
+                let develop = ConversionRequest {
+                    output: &developed,
+                    output_format: ImageFormat::Tiff,
+                    options: EncoderOptions::default(),
+                    ..request.clone()
+                };
+                self.darktable.convert(&develop, &ctx.cancel).await?;
+                record_timing(&["develop"], self.darktable.name(), step_started.elapsed());
+                source = ConversionRequest {
+                    input: &developed,
+                    input_format: ImageFormat::Tiff,
+                    ..request.clone()
+                };
+            }
+            
+            let mut ops: Vec<&str> = pipeline.operations.iter().map(Step::name).collect();
+            ops.push("encode");
+            ctx.progress.report(
+                invocations as u64,
+                Some(phases),
+                format!("Running {} with imagemagick", ops.join(", ")),
+            );
+            let step_started = Instant::now();
+            dimensions = self.imagemagick.process(&source, pipeline.operations, &ctx.cancel).await?;
+            record_timing(&ops, self.imagemagick.name(), step_started.elapsed());
+        }
+        drop(intermediates);
+        
+        ctx.progress.report(phases - 1, Some(phases), "Verifying output");
+        let output_bytes = Self::verify_output(&output_path).await?;
+        self.outputs.record(&output_path);
+        let input_bytes = tokio::fs::metadata(&input_path).await.map(|m| m.len()).unwrap_or(0);
+        if dimensions.is_none() {
+            dimensions = probe::image_dimensions(&output_path).await;
+        }
+        ctx.progress.report(phases, Some(phases), "Pipeline complete");
+        
+        Ok(PipelineResult {
             success: true,
             message: format!(
-                "Successfully converted {} to {} using {}",
+                "Processed {} to {} in {} converter invocation{}",
                 args.input_path,
                 args.output_path,
-                converter.name()
+                invocations,
+                if invocations == 1 { "" } else { "s" }
             ),
             output_path: args.output_path,
+            format: output_format,
+            input_bytes,
+            output_bytes,
+            width: dimensions.map(|(w, _)| w),
+            height: dimensions.map(|(_, h)| h),
+            invocations,
+            steps,
+            duration_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+    
+    /// Wait for a free conversion slot, or for the request to be cancelled
+    async fn conversion_slot(&self, ctx: &RequestContext) -> Result<SemaphorePermit<'_>> {
+        tokio::select! {
+            permit = self.conversion_slots.acquire() => permit
+                .map_err(|e| McpImageError::Mcp(format!("Conversion queue closed: {}", e))),
+            _ = ctx.cancel.cancelled() => Err(McpImageError::Cancelled),
+        }
+    }
+    
+    /// Run ImageMagick with the transform, developing the input with
+    /// darktable into a temporary TIFF first if asked to. Returns the final
+    /// dimensions ImageMagick reported.
+    async fn apply_transform(
+        &self,
+        request: &ConversionRequest<'_>,
+        step: &TransformStep,
+        ctx: &RequestContext,
+    ) -> Result<Option<(u32, u32)>> {
+        if !step.develop {
+            return self.imagemagick.transform(request, &step.transform, &ctx.cancel).await;
+        }
+        
+        let dir = tempfile::tempdir()?;
+        let developed = dir.path().join("developed.tif");
+        let develop = ConversionRequest {
+            input: request.input,
+            input_format: request.input_format,
+            output: &developed,
+            output_format: ImageFormat::Tiff,
+            options: EncoderOptions::default(),
         };
+        self.darktable.convert(&develop, &ctx.cancel).await?;
         
-        Ok(serde_json::to_value(result)?)
+        let request = ConversionRequest {
+            input: &developed,
+            input_format: ImageFormat::Tiff,
+            ..request.clone()
+        };
+        self.imagemagick.transform(&request, &step.transform, &ctx.cancel).await
+    }
+    
+    /// The explicit `format`, or the one implied by the output extension;
+    /// the two must agree when both are given
+    pub(super) fn output_format(output_path: &Path, format: Option<ImageFormat>) -> Result<ImageFormat> {
+        let implied = ImageFormat::from_path(output_path);
+        let format = match (format, implied) {
+            (Some(format), Some(implied)) if format != implied => {
+                return Err(McpImageError::InvalidInput(format!(
+                    "Output path {} does not match format {}",
+                    output_path.display(),
+                    format
+                )));
+            }
+            (Some(format), _) | (None, Some(format)) => format,
+            (None, None) => {
+                return Err(McpImageError::InvalidInput(format!(
+                    "Cannot tell the output format of {}; pass format",
+                    output_path.display()
+                )));
+            }
+        };
+        if !ImageFormat::OUTPUTS.contains(&format) {
+            return Err(McpImageError::InvalidInput(format!("{} is not an output format", format)));
+        }
+        Ok(format)
     }
     
-    pub async fn check_converters(&self, _args: CheckConvertersArgs) -> Result<Value> {
+    /// Preview of a finished conversion from the converter that produced it,
+    /// rejected if it exceeds the size cap
+    async fn render_preview(
+        converter: &dyn ImageConverter,
+        input_path: &Path,
+        output_path: &Path,
+        ctx: &RequestContext,
+    ) -> Result<Preview> {
+        let preview = converter
+            .create_preview(input_path, output_path, PREVIEW_MAX_DIMENSION, &ctx.cancel)
+            .await?;
+        if preview.data.len() > PREVIEW_MAX_BYTES {
+            return Err(McpImageError::ConversionFailed(format!(
+                "Preview is {} bytes, over the {} byte limit",
+                preview.data.len(),
+                PREVIEW_MAX_BYTES
+            )));
+        }
+        Ok(preview)
+    }
+    
+    /// Make sure the converter actually produced a non-empty file, returning
+    /// its size
+    async fn verify_output(output_path: &Path) -> Result<u64> {
+        match tokio::fs::metadata(output_path).await {
+            Ok(metadata) if metadata.len() > 0 => Ok(metadata.len()),
+            Ok(_) => Err(McpImageError::ConversionFailed(format!(
+                "Converter produced an empty file: {}",
+                output_path.display()
+            ))),
+            Err(_) => Err(McpImageError::ConversionFailed(format!(
+                "Converter reported success but no output was written to {}",
+                output_path.display()
+            ))),
+        }
+    }
+    
+    pub async fn check_converters(&self, _args: CheckConvertersArgs) -> Result<CheckConvertersResult> {
+        let imagemagick_available = self.imagemagick.is_available();
+        let missing_delegates = if imagemagick_available {
+            self.imagemagick.missing_delegates().await.unwrap_or_else(|e| {
+                tracing::warn!("Cannot list ImageMagick formats: {}", e);
+                Vec::new()
+            })
+        } else {
+            Vec::new()
+        };
+        
         let converters = vec![
             ConverterInfo {
                 name: "imagemagick".to_string(),
-                available: self.imagemagick.is_available(),
+                available: imagemagick_available,
+                missing_delegates,
             },
             ConverterInfo {
                 name: "darktable".to_string(),
                 available: self.darktable.is_available(),
+                missing_delegates: Vec::new(),
             },
         ];
         
         let available_count = converters.iter().filter(|c| c.available).count();
         
-        let result = CheckConvertersResult {
+        Ok(CheckConvertersResult {
             converters,
             available_count,
-        };
-        
-        Ok(serde_json::to_value(result)?)
-    }
-    
-    pub fn get_convert_tool_schema() -> Value {
-        json!({
-            "name": "convert_dng_to_webp",
-            "description": "Convert DNG image to WebP format without loss",
-            "inputSchema": {
-                "type": "object",
-                "properties": {
-                    "input_path": {
-                        "type": "string",
-                        "description": "Path to DNG file"
-                    },
-                    "output_path": {
-                        "type": "string",
-                        "description": "Path for WebP output"
-                    },
-                    "converter": {
-                        "type": "string",
-                        "enum": ["auto", "imagemagick", "darktable"],
-                        "default": "auto",
-                        "description": "Which converter to use (auto selects the best available)"
-                    }
-                },
-                "required": ["input_path", "output_path"]
-            }
         })
     }
     
-    pub fn get_check_tool_schema() -> Value {
-        json!({
-            "name": "check_converters",
-            "description": "Check which image converters are available on the system",
-            "inputSchema": {
-                "type": "object",
-                "properties": {}
-            }
-        })
+    /// The tools this handler serves
+    pub fn tool_registry(self: &Arc<Self>) -> ToolRegistry {
+        let mut registry = ToolRegistry::new();
+        registry.register(Arc::new(ConvertDngToWebpTool { handler: self.clone() }));
+        registry.register(Arc::new(ConvertImageTool { handler: self.clone() }));
+        registry.register(Arc::new(TransformImageTool { handler: self.clone() }));
+        registry.register(Arc::new(ProcessPipelineTool { handler: self.clone() }));
+        registry.register(Arc::new(BatchConvertTool::new(self.clone())));
+        registry.register(Arc::new(CheckConvertersTool { handler: self.clone() }));
+        registry
+    }
+}
+
+pub struct ConvertDngToWebpTool {
+    handler: Arc<ImageHandler>,
+}
+
+#[async_trait]
+impl Tool for ConvertDngToWebpTool {
+    fn name(&self) -> &'static str {
+        "convert_dng_to_webp"
     }
     
-    pub async fn handle_tool_call(&self, name: &str, arguments: Value) -> Result<Value> {
-        match name {
-            "convert_dng_to_webp" => {
-                let args: ConvertDngToWebpArgs = serde_json::from_value(arguments)
-                    .map_err(|e| McpImageError::Mcp(format!("Invalid params: {}", e)))?;
-                
-                let result = self.convert_dng_to_webp(args).await?;
-                
-                // Extract the message from the result
-                let message = if let Some(msg) = result.get("message").and_then(|m| m.as_str()) {
-                    msg.to_string()
-                } else {
-                    serde_json::to_string_pretty(&result).unwrap_or_else(|_| "Conversion completed".to_string())
-                };
-                
-                // Return in MCP content array format
-                Ok(json!({
-                    "content": [{
-                        "type": "text",
-                        "text": message
-                    }]
-                }))
-            }
-            "check_converters" => {
-                let args: CheckConvertersArgs = serde_json::from_value(arguments)
-                    .map_err(|e| McpImageError::Mcp(format!("Invalid params: {}", e)))?;
-                
-                let result = self.check_converters(args).await?;
-                
-                // Format the result as readable text
-                let mut text = String::new();
-                if let Some(converters) = result.get("converters").and_then(|c| c.as_array()) {
-                    text.push_str("Available converters:\n");
-                    for converter in converters {
-                        if let (Some(name), Some(available)) = (
-                            converter.get("name").and_then(|n| n.as_str()),
-                            converter.get("available").and_then(|a| a.as_bool())
-                        ) {
-                            text.push_str(&format!("- {}: {}\n", name, if available { "Available" } else { "Not available" }));
-                        }
-                    }
-                    
-                    if let Some(count) = result.get("available_count").and_then(|c| c.as_u64()) {
-                        text.push_str(&format!("\nTotal available: {}", count));
-                    }
-                } else {
-                    text = serde_json::to_string_pretty(&result).unwrap_or_else(|_| "Check completed".to_string());
-                }
-                
-                // Return in MCP content array format
-                Ok(json!({
-                    "content": [{
-                        "type": "text",
-                        "text": text
-                    }]
-                }))
+    fn title(&self) -> &'static str {
+        "Convert DNG to WebP"
+    }
+    
+    fn description(&self) -> &'static str {
+        "Convert DNG image to WebP format, losslessly unless `webp` options ask for lossy compression"
+    }
+    
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::WRITES_OUTPUT
+    }
+    
+    fn input_schema(&self) -> Value {
+        schema_for::<ConvertDngToWebpArgs>()
+    }
+    
+    fn output_schema(&self) -> Option<Value> {
+        Some(schema_for::<ConversionResult>())
+    }
+    
+    fn is_available(&self) -> bool {
+        self.handler.has_converter()
+    }
+    
+    async fn call(&self, arguments: Value, ctx: &RequestContext) -> Result<Value> {
+        let args: ConvertDngToWebpArgs = parse_arguments(arguments)?;
+        let result = self.handler.convert_dng_to_webp(args, ctx).await?;
+        conversion_tool_result(&result)
+    }
+}
+
+pub struct ConvertImageTool {
+    handler: Arc<ImageHandler>,
+}
+
+#[async_trait]
+impl Tool for ConvertImageTool {
+    fn name(&self) -> &'static str {
+        "convert_image"
+    }
+    
+    fn title(&self) -> &'static str {
+        "Convert image"
+    }
+    
+    fn description(&self) -> &'static str {
+        "Convert an image (including DNG) to PNG, JPEG, TIFF, WebP, AVIF, JPEG XL, HEIC, GIF or BMP, \
+         chosen by `format` or the output extension"
+    }
+    
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::WRITES_OUTPUT
+    }
+    
+    fn input_schema(&self) -> Value {
+        schema_for::<ConvertImageArgs>()
+    }
+    
+    fn output_schema(&self) -> Option<Value> {
+        Some(schema_for::<ConversionResult>())
+    }
+    
+    fn is_available(&self) -> bool {
+        self.handler.has_converter()
+    }
+    
+    async fn call(&self, arguments: Value, ctx: &RequestContext) -> Result<Value> {
+        let args: ConvertImageArgs = parse_arguments(arguments)?;
+        let result = self.handler.convert_image(args, ctx).await?;
+        conversion_tool_result(&result)
+    }
+}
+
+pub struct TransformImageTool {
+    handler: Arc<ImageHandler>,
+}
+
+#[async_trait]
+impl Tool for TransformImageTool {
+    fn name(&self) -> &'static str {
+        "transform_image"
+    }
+    
+    fn title(&self) -> &'static str {
+        "Transform image"
+    }
+    
+    fn description(&self) -> &'static str {
+        "Auto-orient, crop, resize, rotate and flip an image in a single ImageMagick pass, optionally \
+         after developing it with darktable, and report the final dimensions"
+    }
+    
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::WRITES_OUTPUT
+    }
+    
+    fn input_schema(&self) -> Value {
+        schema_for::<TransformImageArgs>()
+    }
+    
+    fn output_schema(&self) -> Option<Value> {
+        Some(schema_for::<ConversionResult>())
+    }
+    
+    fn is_available(&self) -> bool {
+        self.handler.imagemagick.is_available()
+    }
+    
+    async fn call(&self, arguments: Value, ctx: &RequestContext) -> Result<Value> {
+        let args: TransformImageArgs = parse_arguments(arguments)?;
+        let result = self.handler.transform_image(args, ctx).await?;
+        conversion_tool_result(&result)
+    }
+}
+
+pub struct ProcessPipelineTool {
+    handler: Arc<ImageHandler>,
+}
+
+#[async_trait]
+impl Tool for ProcessPipelineTool {
+    fn name(&self) -> &'static str {
+        "process_pipeline"
+    }
+    
+    fn title(&self) -> &'static str {
+        "Process image pipeline"
+    }
+    
+    fn description(&self) -> &'static str {
+        "Apply a list of steps (develop, resize, sharpen, color_convert, watermark, encode) to an image \
+         in as few converter runs as possible, without intermediate files to manage; reports per-step timing"
+    }
+    
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::WRITES_OUTPUT
+    }
+    
+    fn input_schema(&self) -> Value {
+        schema_for::<ProcessPipelineArgs>()
+    }
+    
+    fn output_schema(&self) -> Option<Value> {
+        Some(schema_for::<PipelineResult>())
+    }
+    
+    fn is_available(&self) -> bool {
+        self.handler.has_converter()
+    }
+    
+    async fn call(&self, arguments: Value, ctx: &RequestContext) -> Result<Value> {
+        let args: ProcessPipelineArgs = parse_arguments(arguments)?;
+        let result = self.handler.process_pipeline(args, ctx).await?;
+        
+        let mut text = result.message.clone();
+        for step in &result.steps {
+            text.push_str(&format!(
+                "\n- {}: {} run {}, {} ms",
+                step.op, step.converter, step.invocation, step.duration_ms
+            ));
+        }
+        Ok(json!({
+            "content": [{
+                "type": "text",
+                "text": text
+            }],
+            "structuredContent": serde_json::to_value(&result)?
+        }))
+    }
+}
+
+pub struct CheckConvertersTool {
+    handler: Arc<ImageHandler>,
+}
+
+#[async_trait]
+impl Tool for CheckConvertersTool {
+    fn name(&self) -> &'static str {
+        "check_converters"
+    }
+    
+    fn title(&self) -> &'static str {
+        "Check converters"
+    }
+    
+    fn description(&self) -> &'static str {
+        "Check which image converters are available on the system"
+    }
+    
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::READ_ONLY
+    }
+    
+    fn input_schema(&self) -> Value {
+        schema_for::<CheckConvertersArgs>()
+    }
+    
+    fn output_schema(&self) -> Option<Value> {
+        Some(schema_for::<CheckConvertersResult>())
+    }
+    
+    async fn call(&self, arguments: Value, _ctx: &RequestContext) -> Result<Value> {
+        let args: CheckConvertersArgs = parse_arguments(arguments)?;
+        let result = self.handler.check_converters(args).await?;
+        
+        // Format the result as readable text
+        let mut text = String::from("Available converters:\n");
+        for converter in &result.converters {
+            text.push_str(&format!(
+                "- {}: {}",
+                converter.name,
+                if converter.available { "Available" } else { "Not available" }
+            ));
+            if !converter.missing_delegates.is_empty() {
+                let formats: Vec<String> = converter.missing_delegates.iter().map(|f| f.to_string()).collect();
+                text.push_str(&format!(" (no delegate for {})", formats.join(", ")));
             }
-            _ => Err(McpImageError::Mcp(format!(
-                "Unknown tool: {}",
-                name
-            )))
+            text.push('\n');
         }
+        text.push_str(&format!("\nTotal available: {}", result.available_count));
+        
+        // Return in MCP content array format
+        Ok(json!({
+            "content": [{
+                "type": "text",
+                "text": text
+            }],
+            "structuredContent": serde_json::to_value(&result)?
+        }))
+    }
+}
+
+/// MCP tool result for a conversion: a text message, a note if a requested
+/// preview is missing, the preview image and `structuredContent`
+fn conversion_tool_result(result: &ConversionResult) -> Result<Value> {
+    // Return in MCP content array format
+    let mut content = vec![json!({
+        "type": "text",
+        "text": result.message
+    })];
+    if let Some(error) = &result.preview_error {
+        content.push(json!({
+            "type": "text",
+            "text": format!("Preview unavailable: {}", error)
+        }));
     }
-}
\ No newline at end of file
+    if let Some(preview) = &result.preview {
+        content.push(json!({
+            "type": "image",
+            "data": base64::engine::general_purpose::STANDARD.encode(&preview.data),
+            "mimeType": preview.mime_type
+        }));
+    }
+    
+    Ok(json!({
+        "content": content,
+        "structuredContent": serde_json::to_value(result)?
+    }))
+}
@@ -1,3 +1,15 @@
+pub mod batch;
+pub mod context;
 pub mod image;
+pub mod probe;
+pub mod prompts;
+pub mod resources;
+pub mod tools;
+pub mod watcher;
 
-pub use image::ImageHandler;
\ No newline at end of file
+pub use context::{ProgressReporter, RequestContext};
+pub use image::ImageHandler;
+pub use prompts::PromptHandler;
+pub use resources::{OutputLog, ResourceHandler};
+pub use tools::{Tool, ToolAnnotations, ToolRegistry, DEFAULT_PAGE_SIZE};
+pub use watcher::ResourceWatcher;
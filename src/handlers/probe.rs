@@ -0,0 +1,95 @@
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Bytes read from the start of a file when looking for its dimensions.
+/// JPEG frame headers can follow large EXIF blocks.
+const PROBE_BYTES: u64 = 256 * 1024;
+
+/// Width and height of an image, read from its header without decoding it.
+/// Returns `None` for formats it does not understand or malformed files.
+pub async fn image_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let file = tokio::fs::File::open(path).await.ok()?;
+    let mut header = Vec::new();
+    file.take(PROBE_BYTES).read_to_end(&mut header).await.ok()?;
+    dimensions_from_header(&header)
+}
+
+fn dimensions_from_header(data: &[u8]) -> Option<(u32, u32)> {
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        webp_dimensions(data)
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some((be_u32(data, 16)?, be_u32(data, 20)?))
+    } else if data.starts_with(b"\xff\xd8") {
+        jpeg_dimensions(data)
+    } else if data.starts_with(b"GIF8") {
+        Some((le_u16(data, 6)? as u32, le_u16(data, 8)? as u32))
+    } else if data.starts_with(b"BM") {
+        let width = le_u32(data, 18)? as i32;
+        let height = le_u32(data, 22)? as i32;
+        Some((width.unsigned_abs(), height.unsigned_abs()))
+    } else {
+        None
+    }
+}
+
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        // Lossy: frame header after the 3-byte frame tag and start code
+        b"VP8 " => {
+            let width = le_u16(data, 26)? & 0x3fff;
+            let height = le_u16(data, 28)? & 0x3fff;
+            Some((width as u32, height as u32))
+        }
+        // Lossless: 14-bit width and height minus one, packed after the signature
+        b"VP8L" => {
+            let bits = le_u32(data, 21)?;
+            Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+        }
+        // Extended: 24-bit canvas width and height minus one
+        b"VP8X" => Some((le_u24(data, 24)? + 1, le_u24(data, 27)? + 1)),
+        _ => None,
+    }
+}
+
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut pos = 2;
+    loop {
+        // Markers may be padded with any number of 0xff bytes
+        while *data.get(pos)? == 0xff && *data.get(pos + 1)? == 0xff {
+            pos += 1;
+        }
+        if *data.get(pos)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        let length = be_u16(data, pos + 2)? as usize;
+        let is_frame_header = matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc);
+        if is_frame_header {
+            let height = be_u16(data, pos + 5)?;
+            let width = be_u16(data, pos + 7)?;
+            return Some((width as u32, height as u32));
+        }
+        pos += 2 + length;
+    }
+}
+
+fn be_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le_u24(data: &[u8], at: usize) -> Option<u32> {
+    let bytes = data.get(at..at + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+fn le_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::{Result, McpImageError};
+
+/// Workflow templates shipped with the server, in the same format as
+/// templates loaded from a prompts directory
+const BUILTIN_PROMPTS: &[&str] = &[
+    include_str!("../../prompts/archive_raw.json"),
+    include_str!("../../prompts/publish_web.json"),
+    include_str!("../../prompts/contact_sheet.json"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+    /// Substituted when the client leaves the argument out; not listed
+    #[serde(default, skip_serializing)]
+    pub default: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptMessage {
+    #[serde(default = "default_role")]
+    pub role: String,
+    /// Message text with `{{argument}}` placeholders
+    pub text: String,
+}
+
+fn default_role() -> String {
+    "user".to_string()
+}
+
+/// A prompt template as stored in a JSON file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub arguments: Vec<PromptArgument>,
+    pub messages: Vec<PromptMessage>,
+}
+
+impl PromptTemplate {
+    fn parse(source: &str) -> Result<Self> {
+        let template: Self = serde_json::from_str(source)?;
+        if template.name.trim().is_empty() {
+            return Err(McpImageError::InvalidInput("prompt name is empty".to_string()));
+        }
+        if let Some(message) = template.messages.iter().find(|m| m.role != "user" && m.role != "assistant") {
+            return Err(McpImageError::InvalidInput(format!(
+                "prompt {} has a message with unknown role {:?}",
+                template.name, message.role
+            )));
+        }
+        Ok(template)
+    }
+    
+    /// Entry for `prompts/list`
+    fn describe(&self) -> Value {
+        let mut prompt = json!({
+            "name": self.name,
+            "arguments": self.arguments,
+        });
+        if let Some(title) = &self.title {
+            prompt["title"] = json!(title);
+        }
+        if let Some(description) = &self.description {
+            prompt["description"] = json!(description);
+        }
+        prompt
+    }
+    
+    /// Fill in the placeholders, failing on missing required arguments
+    fn render(&self, arguments: &Map<String, Value>) -> Result<Value> {
+        let mut values = BTreeMap::new();
+        for argument in &self.arguments {
+            let value = match arguments.get(&argument.name) {
+                Some(Value::String(s)) => Some(s.clone()),
+                Some(Value::Null) | None => argument.default.clone(),
+                Some(other) => Some(other.to_string()),
+            };
+            match value {
+                Some(value) => {
+                    values.insert(argument.name.as_str(), value);
+                }
+                None if argument.required => {
+                    return Err(McpImageError::InvalidParams(format!(
+                        "prompt {} requires argument {}",
+                        self.name, argument.name
+                    )));
+                }
+                None => {
+                    values.insert(argument.name.as_str(), String::new());
+                }
+            }
+        }
+        
+        let messages: Vec<Value> = self
+            .messages
+            .iter()
+            .map(|message| {
+                let text = substitute(&message.text, &values);
+                json!({
+                    "role": message.role,
+                    "content": { "type": "text", "text": text }
+                })
+            })
+            .collect();
+        
+        let mut result = json!({ "messages": messages });
+        if let Some(description) = &self.description {
+            result["description"] = json!(description);
+        }
+        Ok(result)
+    }
+}
+
+/// Replace each `{{name}}` in `text` with its value in a single pass, so
+/// placeholders inside substituted values are left as they are. Unknown
+/// placeholders are kept verbatim.
+fn substitute(text: &str, values: &BTreeMap<&str, String>) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = &rest[start + 2..start + 2 + end];
+        rendered.push_str(&rest[..start]);
+        match values.get(name) {
+            Some(value) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &rest[start + end + 4..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Serves `prompts/list` and `prompts/get`
+pub struct PromptHandler {
+    prompts: BTreeMap<String, PromptTemplate>,
+}
+
+impl Default for PromptHandler {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl PromptHandler {
+    /// Built-in templates, plus every `*.json` template in `dir`. Templates
+    /// from the directory replace built-ins of the same name.
+    pub fn new(dir: Option<&Path>) -> Self {
+        let mut prompts = BTreeMap::new();
+        for source in BUILTIN_PROMPTS {
+            match PromptTemplate::parse(source) {
+                Ok(template) => {
+                    prompts.insert(template.name.clone(), template);
+                }
+                Err(e) => tracing::error!("Invalid built-in prompt: {}", e),
+            }
+        }
+        
+        if let Some(dir) = dir {
+            Self::load_dir(dir, &mut prompts);
+        }
+        
+        Self { prompts }
+    }
+    
+    fn load_dir(dir: &Path, prompts: &mut BTreeMap<String, PromptTemplate>) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!("Cannot read prompts directory {}: {}", dir.display(), e);
+                return;
+            }
+        };
+        
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        
+        for path in paths {
+            let loaded = std::fs::read_to_string(&path)
+                .map_err(McpImageError::from)
+                .and_then(|source| PromptTemplate::parse(&source));
+            match loaded {
+                Ok(template) => {
+                    tracing::info!("Loaded prompt {} from {}", template.name, path.display());
+                    prompts.insert(template.name.clone(), template);
+                }
+                Err(e) => tracing::warn!("Skipping prompt {}: {}", path.display(), e),
+            }
+        }
+    }
+    
+    /// `prompts/list`
+    pub fn list(&self) -> Value {
+        let prompts: Vec<Value> = self.prompts.values().map(PromptTemplate::describe).collect();
+        json!({ "prompts": prompts })
+    }
+    
+    /// `prompts/get`
+    pub fn get(&self, name: &str, arguments: &Map<String, Value>) -> Result<Value> {
+        let template = self.prompts.get(name).ok_or_else(|| {
+            McpImageError::InvalidParams(format!("unknown prompt {}", name))
+        })?;
+        template.render(arguments)
+    }
+}
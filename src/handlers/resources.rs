@@ -0,0 +1,290 @@
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use base64::Engine;
+use notify::RecursiveMode;
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::{Result, McpImageError};
+use crate::session::Session;
+use super::ResourceWatcher;
+
+/// Number of converted outputs remembered for `resources/list`
+const OUTPUT_LOG_CAPACITY: usize = 256;
+
+/// Entries returned per `resources/list` page
+const PAGE_SIZE: usize = 200;
+
+/// Directory levels walked below each root
+const MAX_DEPTH: usize = 8;
+
+/// Largest file `resources/read` will return inline
+const MAX_READ_BYTES: u64 = 32 * 1024 * 1024;
+
+/// File extensions listed as resources, with their MIME types
+const IMAGE_TYPES: &[(&str, &str)] = &[
+    ("webp", "image/webp"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("tif", "image/tiff"),
+    ("tiff", "image/tiff"),
+    ("gif", "image/gif"),
+    ("bmp", "image/bmp"),
+    ("avif", "image/avif"),
+    ("jxl", "image/jxl"),
+    ("heic", "image/heic"),
+    ("heif", "image/heif"),
+    ("dng", "image/x-adobe-dng"),
+];
+
+/// MIME type of an image file, judged by its extension
+pub fn mime_type_for_path(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    IMAGE_TYPES.iter().find(|(e, _)| *e == ext).map(|(_, mime)| *mime)
+}
+
+/// Recently written conversion outputs, readable as resources even when
+/// they live outside the configured roots
+#[derive(Debug, Default)]
+pub struct OutputLog {
+    paths: Mutex<VecDeque<PathBuf>>,
+    /// Told about every output directory so external changes are noticed
+    watcher: OnceLock<Arc<ResourceWatcher>>,
+}
+
+impl OutputLog {
+    /// Remember a freshly written output file
+    pub fn record(&self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if let (Some(watcher), Some(dir)) = (self.watcher.get(), path.parent()) {
+            watcher.watch(dir, RecursiveMode::NonRecursive);
+        }
+        
+        let mut paths = self.paths.lock().unwrap_or_else(|e| e.into_inner());
+        paths.retain(|p| p != &path);
+        if paths.len() == OUTPUT_LOG_CAPACITY {
+            paths.pop_front();
+        }
+        paths.push_back(path);
+    }
+    
+    /// Known outputs, most recent first
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.paths.lock().unwrap_or_else(|e| e.into_inner()).iter().rev().cloned().collect()
+    }
+    
+    pub fn contains(&self, path: &Path) -> bool {
+        self.paths.lock().unwrap_or_else(|e| e.into_inner()).iter().any(|p| p == path)
+    }
+}
+
+/// A directory whose image files are exposed as `file://` resources
+#[derive(Debug, Clone)]
+pub struct ResourceRoot {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Serves `resources/list`, `resources/read` and `resources/templates/list`,
+/// and watches roots and output directories for subscriptions
+pub struct ResourceHandler {
+    roots: Vec<ResourceRoot>,
+    outputs: Arc<OutputLog>,
+    watcher: Arc<ResourceWatcher>,
+}
+
+impl ResourceHandler {
+    pub fn new(roots: &[PathBuf], outputs: Arc<OutputLog>) -> Self {
+        let roots = roots
+            .iter()
+            .filter_map(|root| match root.canonicalize() {
+                Ok(path) => {
+                    let name = path
+                        .file_name()
+                        .and_then(|n| n.to_str())
+                        .unwrap_or("root")
+                        .to_string();
+                    Some(ResourceRoot { name, path })
+                }
+                Err(e) => {
+                    tracing::warn!("Ignoring resource root {}: {}", root.display(), e);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        
+        let watcher = Arc::new(ResourceWatcher::new());
+        for root in &roots {
+            watcher.watch(&root.path, RecursiveMode::Recursive);
+        }
+        if outputs.watcher.set(watcher.clone()).is_err() {
+            tracing::warn!("Output log is already watched by another resource handler");
+        }
+        
+        Self { roots, outputs, watcher }
+    }
+    
+    /// Deliver resource change notifications to `session`
+    pub fn register_session(&self, session: &Arc<Session>) {
+        self.watcher.register_session(session);
+    }
+    
+    /// `resources/subscribe`: notify the session when the file changes
+    pub fn subscribe(&self, session: &Session, uri: &str) -> Result<()> {
+        let path = self.resolve(uri)?;
+        if let Some(dir) = path.parent() {
+            self.watcher.watch(dir, RecursiveMode::NonRecursive);
+        }
+        session.subscribe_resource(uri, path);
+        Ok(())
+    }
+    
+    pub fn roots(&self) -> &[ResourceRoot] {
+        &self.roots
+    }
+    
+    /// `resources/list`: image files under every root plus recent outputs,
+    /// paginated with an opaque cursor
+    pub async fn list(&self, cursor: Option<&str>) -> Result<Value> {
+        let offset = match cursor {
+            Some(cursor) => cursor.parse::<usize>().map_err(|_| {
+                McpImageError::InvalidParams(format!("bad cursor {:?}", cursor))
+            })?,
+            None => 0,
+        };
+        
+        let mut paths = self.outputs.paths();
+        for root in &self.roots {
+            paths.extend(Self::walk(&root.path).await);
+        }
+        // Outputs under a root turn up in the walk as well
+        let mut seen = HashSet::new();
+        paths.retain(|path| seen.insert(path.clone()));
+        
+        let mut resources = Vec::new();
+        for path in paths.iter().skip(offset).take(PAGE_SIZE) {
+            if let Some(resource) = Self::describe(path).await {
+                resources.push(resource);
+            }
+        }
+        
+        let mut result = json!({ "resources": resources });
+        if offset + PAGE_SIZE < paths.len() {
+            result["nextCursor"] = json!((offset + PAGE_SIZE).to_string());
+        }
+        Ok(result)
+    }
+    
+    /// `resources/read`: the file behind a `file://` URI, as a base64 blob
+    pub async fn read(&self, uri: &str) -> Result<Value> {
+        let path = self.resolve(uri)?;
+        let mime_type = mime_type_for_path(&path).unwrap_or("application/octet-stream");
+        
+        let metadata = tokio::fs::metadata(&path).await
+            .map_err(|_| McpImageError::ResourceNotFound(uri.to_string()))?;
+        if metadata.len() > MAX_READ_BYTES {
+            return Err(McpImageError::InvalidParams(format!(
+                "resource {} is {} bytes, larger than the {} byte read limit",
+                uri,
+                metadata.len(),
+                MAX_READ_BYTES
+            )));
+        }
+        
+        let bytes = tokio::fs::read(&path).await?;
+        Ok(json!({
+            "contents": [{
+                "uri": uri,
+                "mimeType": mime_type,
+                "blob": base64::engine::general_purpose::STANDARD.encode(bytes)
+            }]
+        }))
+    }
+    
+    /// `resources/templates/list`: one `file://` template per root
+    pub fn templates(&self) -> Value {
+        let templates: Vec<Value> = self
+            .roots
+            .iter()
+            .filter_map(|root| {
+                let base = Url::from_directory_path(&root.path).ok()?;
+                Some(json!({
+                    "uriTemplate": format!("{}{{path}}", base),
+                    "name": root.name,
+                    "description": format!("Image files under {}", root.path.display())
+                }))
+            })
+            .collect();
+        
+        json!({ "resourceTemplates": templates })
+    }
+    
+    /// Map a `file://` URI to a path inside a root or a known output.
+    /// Anything else is reported as not found so nothing outside the roots
+    /// can be probed.
+    pub fn resolve(&self, uri: &str) -> Result<PathBuf> {
+        let not_found = || McpImageError::ResourceNotFound(uri.to_string());
+        
+        let url = Url::parse(uri).map_err(|_| not_found())?;
+        if url.scheme() != "file" {
+            return Err(not_found());
+        }
+        let path = url.to_file_path().map_err(|_| not_found())?;
+        let path = path.canonicalize().map_err(|_| not_found())?;
+        
+        let allowed = self.roots.iter().any(|root| path.starts_with(&root.path))
+            || self.outputs.contains(&path);
+        if allowed && path.is_file() {
+            Ok(path)
+        } else {
+            Err(not_found())
+        }
+    }
+    
+    /// Image files below `root`, breadth first and in name order
+    async fn walk(root: &Path) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        let mut pending = VecDeque::from([(root.to_path_buf(), 0)]);
+        
+        while let Some((dir, depth)) = pending.pop_front() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) => {
+                    tracing::debug!("Skipping unreadable directory {}: {}", dir.display(), e);
+                    continue;
+                }
+            };
+            
+            let mut files = Vec::new();
+            let mut dirs = Vec::new();
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let path = entry.path();
+                match entry.file_type().await {
+                    Ok(t) if t.is_dir() && depth < MAX_DEPTH => dirs.push((path, depth + 1)),
+                    Ok(t) if t.is_file() && mime_type_for_path(&path).is_some() => files.push(path),
+                    _ => {}
+                }
+            }
+            
+            files.sort();
+            dirs.sort();
+            found.extend(files);
+            pending.extend(dirs);
+        }
+        
+        found
+    }
+    
+    async fn describe(path: &Path) -> Option<Value> {
+        let uri = Url::from_file_path(path).ok()?;
+        let size = tokio::fs::metadata(path).await.ok()?.len();
+        Some(json!({
+            "uri": uri.to_string(),
+            "name": path.file_name()?.to_string_lossy(),
+            "mimeType": mime_type_for_path(path).unwrap_or("application/octet-stream"),
+            "size": size
+        }))
+    }
+}
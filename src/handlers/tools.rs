@@ -0,0 +1,243 @@
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use async_trait::async_trait;
+use schemars::generate::SchemaSettings;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::{Result, McpImageError};
+use crate::session::Session;
+use super::RequestContext;
+
+/// Entries returned per `tools/list` page unless configured otherwise
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+type SessionList = Arc<Mutex<Vec<Weak<Session>>>>;
+
+/// Behaviour hints published with a tool so clients can decide which calls
+/// need the user's approval
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToolAnnotations {
+    /// The tool does not modify anything
+    pub read_only: bool,
+    /// The tool may overwrite or delete existing data
+    pub destructive: bool,
+    /// Repeating a call with the same arguments has no further effect
+    pub idempotent: bool,
+    /// The tool talks to systems outside the local machine
+    pub open_world: bool,
+}
+
+impl ToolAnnotations {
+    /// Inspects the system without changing it
+    pub const READ_ONLY: Self = Self {
+        read_only: true,
+        destructive: false,
+        idempotent: true,
+        open_world: false,
+    };
+    
+    /// Writes output files, replacing any existing file at the output path
+    pub const WRITES_OUTPUT: Self = Self {
+        read_only: false,
+        destructive: true,
+        idempotent: true,
+        open_world: false,
+    };
+    
+    pub fn to_json(self, title: &str) -> Value {
+        json!({
+            "title": title,
+            "readOnlyHint": self.read_only,
+            "destructiveHint": self.destructive,
+            "idempotentHint": self.idempotent,
+            "openWorldHint": self.open_world
+        })
+    }
+}
+
+/// A tool callable through `tools/call`
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &'static str;
+    
+    /// Human-readable name shown by clients
+    fn title(&self) -> &'static str;
+    
+    fn description(&self) -> &'static str;
+    
+    fn annotations(&self) -> ToolAnnotations;
+    
+    /// JSON Schema of the arguments; use `schema_for` on the type `call`
+    /// deserializes so the two cannot drift apart
+    fn input_schema(&self) -> Value;
+    
+    /// JSON Schema of `structuredContent`, if the tool returns any
+    fn output_schema(&self) -> Option<Value> {
+        None
+    }
+    
+    /// Whether the tool can run right now, e.g. because a converter it needs
+    /// is installed. Unavailable tools are left out of `tools/list`.
+    fn is_available(&self) -> bool {
+        true
+    }
+    
+    /// Run the tool, returning an MCP tool result (`content`, optionally
+    /// `structuredContent`)
+    async fn call(&self, arguments: Value, ctx: &RequestContext) -> Result<Value>;
+}
+
+/// JSON Schema for `T`, self-contained and without the `$schema` header
+pub fn schema_for<T: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft2020_12()
+        .with(|settings| {
+            settings.inline_subschemas = true;
+            settings.meta_schema = None;
+        })
+        .into_generator();
+    let mut schema = generator.into_root_schema_for::<T>();
+    schema.remove("title");
+    schema.to_value()
+}
+
+/// Deserialize tool arguments, reporting mismatches as invalid params
+pub fn parse_arguments<T: DeserializeOwned>(arguments: Value) -> Result<T> {
+    serde_json::from_value(arguments).map_err(|e| McpImageError::InvalidParams(e.to_string()))
+}
+
+/// The tools a server offers, in listing order
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+    /// Entries per `tools/list` page
+    page_size: usize,
+    /// Sessions told when the set of available tools changes
+    sessions: SessionList,
+}
+
+impl Default for ToolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self {
+            tools: Vec::new(),
+            page_size: DEFAULT_PAGE_SIZE,
+            sessions: SessionList::default(),
+        }
+    }
+    
+    /// List `page_size` tools per `tools/list` page (at least one)
+    pub fn with_page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+    
+    /// Add a tool, replacing any tool with the same name
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        self.tools.retain(|t| t.name() != tool.name());
+        self.tools.push(tool);
+    }
+    
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.iter().find(|t| t.name() == name).cloned()
+    }
+    
+    /// `tools/list`: every available tool, paginated with an opaque cursor
+    pub fn list(&self, cursor: Option<&str>) -> Result<Value> {
+        let offset = match cursor {
+            Some(cursor) => cursor.parse::<usize>().map_err(|_| {
+                McpImageError::InvalidParams(format!("bad cursor {:?}", cursor))
+            })?,
+            None => 0,
+        };
+        
+        let available: Vec<&Arc<dyn Tool>> = self.tools.iter().filter(|t| t.is_available()).collect();
+        let tools: Vec<Value> = available
+            .iter()
+            .skip(offset)
+            .take(self.page_size)
+            .map(|tool| Self::describe(tool.as_ref()))
+            .collect();
+        
+        let mut result = json!({ "tools": tools });
+        if offset + self.page_size < available.len() {
+            result["nextCursor"] = json!((offset + self.page_size).to_string());
+        }
+        Ok(result)
+    }
+    
+    /// Send `notifications/tools/list_changed` to `session` until it is dropped
+    pub fn register_session(&self, session: &Arc<Session>) {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.retain(|s| s.strong_count() > 0);
+        sessions.push(Arc::downgrade(session));
+    }
+    
+    /// Re-check tool availability every `interval` and notify sessions when
+    /// tools appear or disappear (e.g. a converter is installed). Covers the
+    /// tools registered so far; stops once the registry is dropped.
+    pub fn watch_availability(&self, interval: Duration) {
+        let tools = self.tools.clone();
+        let sessions = Arc::downgrade(&self.sessions);
+        let mut shown = available_names(&tools);
+        
+        let spawned = std::thread::Builder::new()
+            .name("tool-availability".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(interval);
+                let Some(sessions) = sessions.upgrade() else {
+                    break;
+                };
+                
+                let current = available_names(&tools);
+                if current == shown {
+                    continue;
+                }
+                tracing::info!("Available tools changed: {}", current.join(", "));
+                shown = current;
+                
+                let sessions: Vec<Arc<Session>> = {
+                    let mut sessions = sessions.lock().unwrap_or_else(|e| e.into_inner());
+                    sessions.retain(|s| s.strong_count() > 0);
+                    sessions.iter().filter_map(Weak::upgrade).collect()
+                };
+                for session in sessions.iter().filter(|s| s.is_initialized()) {
+                    session.outbox().notify("notifications/tools/list_changed", json!({}));
+                }
+            });
+        if let Err(e) = spawned {
+            tracing::warn!("Tool list change notifications disabled: {}", e);
+        }
+    }
+    
+    /// Dispatch a `tools/call`
+    pub async fn call(&self, name: &str, arguments: Value, ctx: &RequestContext) -> Result<Value> {
+        let tool = self
+            .get(name)
+            .ok_or_else(|| McpImageError::InvalidParams(format!("unknown tool {}", name)))?;
+        tool.call(arguments, ctx).await
+    }
+    
+    fn describe(tool: &dyn Tool) -> Value {
+        let mut entry = json!({
+            "name": tool.name(),
+            "title": tool.title(),
+            "description": tool.description(),
+            "inputSchema": tool.input_schema(),
+            "annotations": tool.annotations().to_json(tool.title()),
+        });
+        if let Some(schema) = tool.output_schema() {
+            entry["outputSchema"] = schema;
+        }
+        entry
+    }
+}
+
+fn available_names(tools: &[Arc<dyn Tool>]) -> Vec<&'static str> {
+    tools.iter().filter(|t| t.is_available()).map(|t| t.name()).collect()
+}
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use notify::event::{AccessKind, AccessMode, ModifyKind};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde_json::json;
+
+use crate::session::Session;
+use super::resources::mime_type_for_path;
+
+/// Quiet period after the last filesystem event before notifying clients
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Longest a burst of events (e.g. a large file being written) is held back
+const MAX_DELAY: Duration = Duration::from_secs(1);
+
+type SessionList = Arc<Mutex<Vec<Weak<Session>>>>;
+
+/// Watches resource roots and conversion output directories, turning
+/// filesystem events into `notifications/resources/updated` for subscribed
+/// sessions and `notifications/resources/list_changed` for everyone
+pub struct ResourceWatcher {
+    /// `None` if the platform watcher could not be created
+    watcher: Mutex<Option<RecommendedWatcher>>,
+    /// Directories already watched, and whether recursively
+    watched: Mutex<Vec<(PathBuf, RecursiveMode)>>,
+    sessions: SessionList,
+}
+
+impl std::fmt::Debug for ResourceWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResourceWatcher")
+            .field("watched", &self.watched)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ResourceWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResourceWatcher {
+    pub fn new() -> Self {
+        let sessions: SessionList = Arc::default();
+        let (tx, rx) = mpsc::channel();
+        
+        let watcher = match notify::recommended_watcher(tx) {
+            Ok(watcher) => {
+                let sessions = sessions.clone();
+                let spawned = std::thread::Builder::new()
+                    .name("resource-watcher".to_string())
+                    .spawn(move || dispatch(rx, sessions));
+                match spawned {
+                    Ok(_) => Some(watcher),
+                    Err(e) => {
+                        tracing::warn!("Resource change notifications disabled: {}", e);
+                        None
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Resource change notifications disabled: {}", e);
+                None
+            }
+        };
+        
+        Self {
+            watcher: Mutex::new(watcher),
+            watched: Mutex::new(Vec::new()),
+            sessions,
+        }
+    }
+    
+    /// Send change notifications to `session` until it is dropped
+    pub fn register_session(&self, session: &Arc<Session>) {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.retain(|s| s.strong_count() > 0);
+        sessions.push(Arc::downgrade(session));
+    }
+    
+    /// Watch a directory, unless it is already covered by an existing watch
+    pub fn watch(&self, dir: &Path, mode: RecursiveMode) {
+        let mut watched = self.watched.lock().unwrap_or_else(|e| e.into_inner());
+        let covered = watched.iter().any(|(path, existing)| {
+            path == dir || (*existing == RecursiveMode::Recursive && dir.starts_with(path))
+        });
+        if covered {
+            return;
+        }
+        
+        let mut watcher = self.watcher.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(watcher) = watcher.as_mut() else {
+            return;
+        };
+        match watcher.watch(dir, mode) {
+            Ok(()) => {
+                tracing::debug!("Watching {} for resource changes", dir.display());
+                watched.push((dir.to_path_buf(), mode));
+            }
+            Err(e) => tracing::warn!("Cannot watch {}: {}", dir.display(), e),
+        }
+    }
+}
+
+/// Changes accumulated during one debounce window
+#[derive(Default)]
+struct ChangeBatch {
+    updated: HashSet<PathBuf>,
+    list_changed: bool,
+}
+
+impl ChangeBatch {
+    fn add(&mut self, event: notify::Result<Event>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::debug!("Filesystem watch error: {}", e);
+                return;
+            }
+        };
+        
+        let (content, listing) = match event.kind {
+            EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_)) => {
+                (true, true)
+            }
+            EventKind::Modify(ModifyKind::Metadata(_)) => (false, false),
+            EventKind::Modify(_) | EventKind::Access(AccessKind::Close(AccessMode::Write)) => {
+                (true, false)
+            }
+            _ => (false, false),
+        };
+        
+        for path in event.paths {
+            if mime_type_for_path(&path).is_none() && !path.is_dir() {
+                continue;
+            }
+            self.list_changed |= listing;
+            if content {
+                self.updated.insert(path);
+            }
+        }
+    }
+    
+    fn is_empty(&self) -> bool {
+        self.updated.is_empty() && !self.list_changed
+    }
+    
+    fn flush(self, sessions: &SessionList) {
+        if self.is_empty() {
+            return;
+        }
+        
+        let sessions: Vec<Arc<Session>> = {
+            let mut sessions = sessions.lock().unwrap_or_else(|e| e.into_inner());
+            sessions.retain(|s| s.strong_count() > 0);
+            sessions.iter().filter_map(Weak::upgrade).collect()
+        };
+        
+        for session in sessions.iter().filter(|s| s.is_initialized()) {
+            if self.list_changed {
+                session.outbox().notify("notifications/resources/list_changed", json!({}));
+            }
+            for path in &self.updated {
+                for uri in session.subscribed_uris(path) {
+                    session.outbox().notify("notifications/resources/updated", json!({ "uri": uri }));
+                }
+            }
+        }
+    }
+}
+
+/// Collect events into debounced batches and notify sessions, until the
+/// watcher is dropped
+fn dispatch(events: Receiver<notify::Result<Event>>, sessions: SessionList) {
+    while let Ok(event) = events.recv() {
+        let started = Instant::now();
+        let mut batch = ChangeBatch::default();
+        batch.add(event);
+        
+        while started.elapsed() < MAX_DELAY {
+            match events.recv_timeout(DEBOUNCE) {
+                Ok(event) => batch.add(event),
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        
+        batch.flush(&sessions);
+    }
+}
@@ -1,10 +1,15 @@
+pub mod config;
 pub mod converters;
 pub mod handlers;
+pub mod logging;
 pub mod server;
+pub mod session;
 pub mod transport;
 
+pub use config::ServerConfig;
 pub use server::McpImageServer;
 
+use serde_json::{json, Value};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -21,14 +26,138 @@ pub enum McpImageError {
     #[error("Conversion failed: {0}")]
     ConversionFailed(String),
     
+    /// A converter process exited unsuccessfully
+    #[error("{converter} failed ({}): {stderr}", .exit_status.map_or("killed by signal".to_string(), |c| format!("exit status {}", c)))]
+    ConverterFailed {
+        converter: String,
+        /// `None` if the process was killed by a signal
+        exit_status: Option<i32>,
+        /// Last lines of the converter's diagnostic output
+        stderr: String,
+    },
+    
     #[error("Invalid input: {0}")]
     InvalidInput(String),
     
     #[error("File not found: {0}")]
     FileNotFound(String),
     
+    #[error("Request cancelled")]
+    Cancelled,
+    
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    
+    #[error("Method not found: {0}")]
+    MethodNotFound(String),
+    
+    #[error("Invalid params: {0}")]
+    InvalidParams(String),
+    
+    #[error("Server not initialized: {0}")]
+    NotInitialized(String),
+    
+    #[error("Resource not found: {0}")]
+    ResourceNotFound(String),
+    
     #[error("MCP error: {0}")]
     Mcp(String),
 }
 
+impl McpImageError {
+    /// Whether this is a failure of the tool's work itself (missing file,
+    /// converter error, ...) rather than of the request. Tool failures are
+    /// reported to the model as an `isError` tool result; everything else
+    /// becomes a JSON-RPC error.
+    pub fn is_tool_error(&self) -> bool {
+        matches!(
+            self,
+            McpImageError::Io(_)
+                | McpImageError::ConverterNotAvailable(_)
+                | McpImageError::ConversionFailed(_)
+                | McpImageError::ConverterFailed { .. }
+                | McpImageError::InvalidInput(_)
+                | McpImageError::FileNotFound(_)
+        )
+    }
+    
+    /// JSON-RPC error code. These are stable; clients may branch on them.
+    pub fn code(&self) -> i32 {
+        match self {
+            McpImageError::InvalidRequest(_) => -32600,
+            McpImageError::MethodNotFound(_) => -32601,
+            McpImageError::InvalidParams(_) => -32602,
+            McpImageError::NotInitialized(_) => -32000,
+            McpImageError::ConverterNotAvailable(_) => -32001,
+            McpImageError::ResourceNotFound(_) => -32002,
+            McpImageError::ConversionFailed(_) | McpImageError::ConverterFailed { .. } => -32003,
+            McpImageError::InvalidInput(_) => -32004,
+            McpImageError::FileNotFound(_) => -32005,
+            McpImageError::Cancelled => -32800,
+            McpImageError::Io(_) | McpImageError::Json(_) | McpImageError::Mcp(_) => -32603,
+        }
+    }
+    
+    /// Stable machine-readable name of the error kind
+    pub fn kind(&self) -> &'static str {
+        match self {
+            McpImageError::Io(_) => "io",
+            McpImageError::Json(_) => "json",
+            McpImageError::ConverterNotAvailable(_) => "converter_not_available",
+            McpImageError::ConversionFailed(_) => "conversion_failed",
+            McpImageError::ConverterFailed { .. } => "converter_failed",
+            McpImageError::InvalidInput(_) => "invalid_input",
+            McpImageError::FileNotFound(_) => "file_not_found",
+            McpImageError::Cancelled => "cancelled",
+            McpImageError::InvalidRequest(_) => "invalid_request",
+            McpImageError::MethodNotFound(_) => "method_not_found",
+            McpImageError::InvalidParams(_) => "invalid_params",
+            McpImageError::NotInitialized(_) => "not_initialized",
+            McpImageError::ResourceNotFound(_) => "resource_not_found",
+            McpImageError::Mcp(_) => "internal",
+        }
+    }
+    
+    /// Short message for the JSON-RPC `error.message` field
+    pub fn summary(&self) -> &'static str {
+        match self {
+            McpImageError::InvalidRequest(_) => "Invalid Request",
+            McpImageError::MethodNotFound(_) => "Method not found",
+            McpImageError::InvalidParams(_) => "Invalid params",
+            McpImageError::NotInitialized(_) => "Server not initialized",
+            McpImageError::ResourceNotFound(_) => "Resource not found",
+            McpImageError::Cancelled => "Request cancelled",
+            McpImageError::ConverterNotAvailable(_) => "Converter not available",
+            McpImageError::ConversionFailed(_) | McpImageError::ConverterFailed { .. } => "Conversion failed",
+            McpImageError::InvalidInput(_) => "Invalid input",
+            McpImageError::FileNotFound(_) => "File not found",
+            McpImageError::Io(_) | McpImageError::Json(_) | McpImageError::Mcp(_) => "Internal error",
+        }
+    }
+    
+    /// Structured `error.data`: the kind, the full message and, for
+    /// converter failures, the converter, exit status and stderr tail
+    pub fn data(&self) -> Value {
+        let mut data = json!({
+            "kind": self.kind(),
+            "detail": self.to_string(),
+        });
+        if let McpImageError::ConverterFailed { converter, exit_status, stderr } = self {
+            data["converter"] = json!(converter);
+            data["exit_status"] = json!(exit_status);
+            data["stderr"] = json!(stderr);
+        }
+        data
+    }
+    
+    /// The complete JSON-RPC `error` object
+    pub fn to_jsonrpc_error(&self) -> Value {
+        json!({
+            "code": self.code(),
+            "message": self.summary(),
+            "data": self.data(),
+        })
+    }
+}
+
 pub type Result<T> = std::result::Result<T, McpImageError>;
\ No newline at end of file
@@ -0,0 +1,274 @@
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use serde_json::json;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::session::{LifecycleState, Session};
+
+/// Target for per-message wire logging. Events on it are never forwarded
+/// to clients, otherwise sending a log notification would log itself.
+pub const WIRE_TARGET: &str = "mcp_imagemagick::wire";
+
+/// Only events from this crate are forwarded to clients
+const CRATE_TARGET: &str = "mcp_imagemagick";
+
+/// Span field naming the session a request belongs to. Events inside such a
+/// span are only forwarded to that session.
+pub const SESSION_FIELD: &str = "session";
+
+/// Span field naming the session whose connection a task serves. Events
+/// inside such a span, and outside any request span, are forwarded to every
+/// session but that one, so a broken connection never reports to itself.
+pub const ORIGIN_FIELD: &str = "origin";
+
+/// Log severities from RFC 5424, as used by `logging/setLevel`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LogLevel {
+    /// Level forwarded to clients that never called `logging/setLevel`
+    pub const DEFAULT: LogLevel = LogLevel::Warning;
+    
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Notice => "notice",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+            LogLevel::Critical => "critical",
+            LogLevel::Alert => "alert",
+            LogLevel::Emergency => "emergency",
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "notice" => Ok(LogLevel::Notice),
+            "warning" => Ok(LogLevel::Warning),
+            "error" => Ok(LogLevel::Error),
+            "critical" => Ok(LogLevel::Critical),
+            "alert" => Ok(LogLevel::Alert),
+            "emergency" => Ok(LogLevel::Emergency),
+            other => Err(format!("Unknown log level: {}", other)),
+        }
+    }
+}
+
+impl From<&Level> for LogLevel {
+    fn from(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warning,
+            Level::INFO => LogLevel::Info,
+            Level::DEBUG | Level::TRACE => LogLevel::Debug,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Sessions that receive forwarded log events
+fn sessions() -> &'static Mutex<Vec<Weak<Session>>> {
+    static SESSIONS: OnceLock<Mutex<Vec<Weak<Session>>>> = OnceLock::new();
+    SESSIONS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Start forwarding log events to `session`. The registration ends when the
+/// session is dropped.
+pub fn register_session(session: &Arc<Session>) {
+    let mut sessions = sessions().lock().unwrap_or_else(|e| e.into_inner());
+    sessions.retain(|s| s.strong_count() > 0);
+    sessions.push(Arc::downgrade(session));
+}
+
+/// Tracing layer that forwards this crate's events as
+/// `notifications/message`, honouring each session's `logging/setLevel`
+/// choice. Events raised while handling a request go to the requesting
+/// session only; anything else goes to every session except the one whose
+/// connection raised it.
+pub struct ClientLogLayer;
+
+/// Span extension recording the session a request span belongs to
+struct SessionScope(u64);
+
+/// Span extension recording the session a connection span serves
+struct OriginScope(u64);
+
+/// Which sessions an event is forwarded to
+#[derive(Clone, Copy)]
+enum Audience {
+    All,
+    Only(u64),
+    AllBut(u64),
+}
+
+impl Audience {
+    fn includes(self, session: &Session) -> bool {
+        match self {
+            Audience::All => true,
+            Audience::Only(id) => session.id() == id,
+            Audience::AllBut(id) => session.id() != id,
+        }
+    }
+}
+
+impl ClientLogLayer {
+    /// The layer with its filter attached, ready to add to a registry
+    pub fn layer<S>() -> impl Layer<S>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        ClientLogLayer.with_filter(filter_fn(Self::forwardable))
+    }
+    
+    fn forwardable(metadata: &Metadata<'_>) -> bool {
+        let target = metadata.target();
+        target.starts_with(CRATE_TARGET)
+            && target != WIRE_TARGET
+            && *metadata.level() <= Level::DEBUG
+    }
+}
+
+impl<S> Layer<S> for ClientLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = ScopeVisitor::default();
+        attrs.record(&mut visitor);
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(session) = visitor.session {
+            span.extensions_mut().insert(SessionScope(session));
+        }
+        if let Some(origin) = visitor.origin {
+            span.extensions_mut().insert(OriginScope(origin));
+        }
+    }
+    
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let level = LogLevel::from(event.metadata().level());
+        // The innermost span naming a session decides
+        let audience = ctx
+            .event_scope(event)
+            .and_then(|scope| {
+                scope.into_iter().find_map(|span| {
+                    let extensions = span.extensions();
+                    match (extensions.get::<SessionScope>(), extensions.get::<OriginScope>()) {
+                        (Some(session), _) => Some(Audience::Only(session.0)),
+                        (None, Some(origin)) => Some(Audience::AllBut(origin.0)),
+                        (None, None) => None,
+                    }
+                })
+            })
+            .unwrap_or(Audience::All);
+        
+        let targets: Vec<Arc<Session>> = {
+            let mut sessions = sessions().lock().unwrap_or_else(|e| e.into_inner());
+            sessions.retain(|s| s.strong_count() > 0);
+            sessions
+                .iter()
+                .filter_map(Weak::upgrade)
+                .filter(|s| audience.includes(s))
+                .filter(|s| s.lifecycle_state() != LifecycleState::Uninitialized)
+                .filter(|s| level >= s.log_level())
+                .collect()
+        };
+        if targets.is_empty() {
+            return;
+        }
+        
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        
+        let params = json!({
+            "level": level.as_str(),
+            "logger": event.metadata().target(),
+            "data": visitor.finish()
+        });
+        for session in targets {
+            session.outbox().notify("notifications/message", params.clone());
+        }
+    }
+}
+
+/// Extracts session ids from request and connection span fields
+#[derive(Default)]
+struct ScopeVisitor {
+    session: Option<u64>,
+    origin: Option<u64>,
+}
+
+impl Visit for ScopeVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            SESSION_FIELD => self.session = Some(value),
+            ORIGIN_FIELD => self.origin = Some(value),
+            _ => {}
+        }
+    }
+    
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+/// Renders an event as its message followed by `key=value` fields
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(self) -> String {
+        if self.fields.is_empty() {
+            self.message
+        } else {
+            format!("{}{}", self.message, self.fields)
+        }
+    }
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+    
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+}
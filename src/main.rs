@@ -1,4 +1,10 @@
-use mcp_imagemagick::McpImageServer;
+use mcp_imagemagick::config::TransportKind;
+use mcp_imagemagick::logging::ClientLogLayer;
+use mcp_imagemagick::transport::HttpTransport;
+use mcp_imagemagick::{McpImageServer, ServerConfig};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -7,8 +13,57 @@ async fn main() -> anyhow::Result<()> {
         eprintln!("MCP server panic: {}", panic_info);
     }));
     
-    let server = McpImageServer::new();
-    if let Err(e) = server.run().await {
+    // Initialize logging to stderr only - stdout belongs to the stdio transport.
+    // Clients additionally receive events at their chosen level in-band.
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_filter(
+                    tracing_subscriber::EnvFilter::from_default_env()
+                        .add_directive(tracing::Level::INFO.into())
+                )
+        )
+        .with(ClientLogLayer::layer())
+        .init();
+    
+    let config = match ServerConfig::from_env_and_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: mcp-imagemagick [--stdio | --http <[addr:]port> | --socket <path>] [--root <path>]... [--prompts <dir>]");
+            std::process::exit(2);
+        }
+    };
+    
+    let transport = config.transport.clone();
+    let allowed_origins = config.http_allowed_origins.clone();
+    let session_timeout = config.http_session_timeout;
+    let bearer_token = config.http_bearer_token.clone();
+    let server = McpImageServer::with_config(config);
+    
+    let result = match transport {
+        TransportKind::Stdio => server.run().await,
+        TransportKind::Http(addr) => match HttpTransport::bind(addr).await {
+            Ok(http) => {
+                http.with_allowed_origins(allowed_origins)
+                    .with_session_timeout(session_timeout)
+                    .with_bearer_token(bearer_token)
+                    .serve(server)
+                    .await
+            }
+            Err(e) => Err(e),
+        },
+        #[cfg(unix)]
+        TransportKind::Unix(path) => server.run_unix(path).await,
+        #[cfg(not(unix))]
+        TransportKind::Unix(_) => {
+            eprintln!("Unix domain sockets are not supported on this platform");
+            std::process::exit(2);
+        }
+    };
+    
+    if let Err(e) = result {
         eprintln!("Server error: {}", e);
         std::process::exit(1);
     }
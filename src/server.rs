@@ -1,36 +1,149 @@
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::Arc;
+use futures::future::join_all;
 use serde_json::{json, Value};
+use tracing::Instrument;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+#[cfg(unix)]
+use tokio::net::UnixListener;
 
-use crate::{Result, McpImageError};
-use crate::handlers::ImageHandler;
-use crate::transport::StdioTransport;
+use crate::{Result, McpImageError, ServerConfig};
+use crate::logging::{self, LogLevel, WIRE_TARGET};
+use crate::handlers::{ImageHandler, ProgressReporter, PromptHandler, RequestContext, ResourceHandler, ToolRegistry};
+use crate::session::{ClientInfo, LifecycleState, ProtocolVersion, Session};
+use crate::transport::{Outbox, StdioTransport, Transport};
+#[cfg(unix)]
+use crate::transport::UnixSocketTransport;
 
 pub struct McpImageServer {
-    handler: ImageHandler,
+    resources: ResourceHandler,
+    prompts: PromptHandler,
+    tools: ToolRegistry,
+}
+
+impl Default for McpImageServer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl McpImageServer {
     pub fn new() -> Self {
-        Self {
-            handler: ImageHandler::new(),
+        Self::with_config(ServerConfig::default())
+    }
+    
+    pub fn with_config(config: ServerConfig) -> Self {
+        let handler = Arc::new(ImageHandler::with_concurrency_limit(config.max_concurrent_conversions));
+        let resources = ResourceHandler::new(&config.resource_roots, handler.output_log());
+        let prompts = PromptHandler::new(config.prompts_dir.as_deref());
+        let tools = handler.tool_registry().with_page_size(config.tools_page_size);
+        if !config.converter_poll_interval.is_zero() {
+            tools.watch_availability(config.converter_poll_interval);
         }
+        Self { resources, prompts, tools }
     }
     
+    /// Route log events and resource and tool change notifications to `session`
+    pub(crate) fn register_session(&self, session: &Arc<Session>) {
+        logging::register_session(session);
+        self.resources.register_session(session);
+        self.tools.register_session(session);
+    }
+    
+    /// Serve a single client over stdin/stdout until EOF
     pub async fn run(self) -> Result<()> {
-        // Initialize logging to stderr only
-        tracing_subscriber::fmt()
-            .with_env_filter(
-                tracing_subscriber::EnvFilter::from_default_env()
-                    .add_directive(tracing::Level::INFO.into())
-            )
-            .with_writer(std::io::stderr)
-            .init();
-        
-        tracing::info!("Starting MCP Image Server");
-        
-        // Main message loop - synchronous
+        tracing::info!("Starting MCP Image Server on stdio");
+        Arc::new(self).serve(StdioTransport::new()).await
+    }
+    
+    /// Accept clients on a Unix domain socket until Ctrl-C, serving each
+    /// connection as its own session
+    #[cfg(unix)]
+    pub async fn run_unix(self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let listener = UnixListener::bind(path)?;
+        tracing::info!("Starting MCP Image Server on unix socket {}", path.display());
+        
+        let server = Arc::new(self);
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, _) = accepted?;
+                    tracing::info!("Accepted unix socket client");
+                    let server = server.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = server.serve(UnixSocketTransport::from_stream(stream)).await {
+                            tracing::error!("Unix socket session failed: {}", e);
+                        }
+                    });
+                }
+                _ = tokio::signal::ctrl_c() => break,
+            }
+        }
+        
+        if let Err(e) = std::fs::remove_file(path) {
+            tracing::warn!("Failed to remove socket {}: {}", path.display(), e);
+        }
+        Ok(())
+    }
+    
+    /// Serve one session over `transport` until it reaches end of stream.
+    ///
+    /// Messages are read on a dedicated task, requests run concurrently and
+    /// all output is funnelled through a single writer task.
+    pub async fn serve<T: Transport>(self: Arc<Self>, transport: T) -> Result<()> {
+        let server = self;
+        let transport = Arc::new(transport);
+        let (outbox, mut outgoing) = Outbox::channel();
+        let session = Arc::new(Session::new(outbox.clone()));
+        server.register_session(&session);
+        // Logs about this connection itself must not be sent over it
+        let connection = tracing::info_span!("connection", origin = session.id());
+        
+        // Writer task - the only place that touches stdout
+        let writer = {
+            let transport = transport.clone();
+            tokio::spawn(async move {
+                while let Some(message) = outgoing.recv().await {
+                    // Log outgoing message (debug level)
+                    tracing::debug!(target: WIRE_TARGET, "Sending message: {}", message);
+                    
+                    if let Err(e) = transport.write_message(&message).await {
+                        // A transport that failed once won't recover; later
+                        // messages are dropped instead of failing in turn
+                        tracing::error!(target: WIRE_TARGET, "Failed to write message, closing writer: {}", e);
+                        break;
+                    }
+                }
+            }.instrument(connection.clone()))
+        };
+        
+        // Reader task - keeps reading while requests are being processed
+        let (incoming_tx, mut incoming) = mpsc::channel::<Value>(64);
+        let reader = {
+            let transport = transport.clone();
+            tokio::spawn(async move {
+                while let Some(message) = transport.read_message().await {
+                    if incoming_tx.send(message).await.is_err() {
+                        break;
+                    }
+                }
+            }.instrument(connection))
+        };
+        
+        // Dispatch loop - each message is handled on its own task
+        let mut handlers = JoinSet::new();
         loop {
-            match StdioTransport::read_message() {
-                Some(message) => {
+            tokio::select! {
+                message = incoming.recv() => {
+                    let Some(message) = message else {
+                        // EOF or error, exit gracefully
+                        tracing::info!("Session ended (EOF or read error)");
+                        break;
+                    };
+                    
                     if message.is_null() {
                         // Empty line, skip
                         tracing::debug!("Received empty line, skipping");
@@ -38,40 +151,129 @@ impl McpImageServer {
                     }
                     
                     // Log incoming message (debug level)
-                    tracing::debug!("Received message: {}", message);
+                    tracing::debug!(target: WIRE_TARGET, "Received message: {}", message);
                     
                     // Check if this is already an error response from transport
                     if message.get("error").is_some() && message.get("jsonrpc").is_some() {
                         // Write the error response directly
-                        if let Err(e) = StdioTransport::write_message(&message) {
-                            tracing::error!("Failed to write error response: {}", e);
-                            // Continue processing - don't break the connection
-                        }
+                        outbox.send(message);
                         continue;
                     }
                     
-                    let response = self.handle_message(message).await;
-                    
-                    // Log outgoing response (debug level)
-                    tracing::debug!("Sending response: {}", response);
-                    
-                    if let Err(e) = StdioTransport::write_message(&response) {
-                        tracing::error!("Failed to write response: {}", e);
-                        // Continue processing - don't break the connection
+                    // Lifecycle messages and notifications are cheap and
+                    // order-sensitive, so handle them before reading on
+                    if Self::is_handled_inline(&message) {
+                        if let Some(response) = server.handle_incoming(&session, message, &outbox).await {
+                            outbox.send(response);
+                        }
+                        continue;
                     }
+                    
+                    let server = server.clone();
+                    let session = session.clone();
+                    let outbox = outbox.clone();
+                    handlers.spawn(async move {
+                        // Notifications produce no response
+                        if let Some(response) = server.handle_incoming(&session, message, &outbox).await {
+                            outbox.send(response);
+                        }
+                    });
                 }
-                None => {
-                    // EOF or error, exit gracefully
-                    tracing::info!("Server shutting down (EOF or read error)");
-                    break;
+                Some(joined) = handlers.join_next(), if !handlers.is_empty() => {
+                    if let Err(e) = joined {
+                        tracing::error!("Request handler task failed: {}", e);
+                    }
                 }
             }
         }
         
+        // Let in-flight requests finish so their responses are written
+        while let Some(joined) = handlers.join_next().await {
+            if let Err(e) = joined {
+                tracing::error!("Request handler task failed: {}", e);
+            }
+        }
+        
+        reader.abort();
+        drop(session);
+        drop(outbox);
+        if let Err(e) = writer.await {
+            tracing::error!("Writer task failed: {}", e);
+        }
+        
         Ok(())
     }
     
-    async fn handle_message(&self, message: Value) -> Value {
+    /// Whether a message must be processed in arrival order rather than on
+    /// its own task
+    pub(crate) fn is_handled_inline(message: &Value) -> bool {
+        // Batches may contain long requests, so they always get a task
+        message.is_object() && message.get("id").is_none()
+            || message.get("method").and_then(|m| m.as_str()) == Some("initialize")
+    }
+    
+    /// Handle a JSON-RPC message or batch, returning what to write back.
+    ///
+    /// Batch elements are dispatched concurrently; the reply array holds one
+    /// response per request (notifications are omitted) and nothing is
+    /// written if the batch contained only notifications.
+    pub(crate) async fn handle_incoming(
+        &self,
+        session: &Session,
+        message: Value,
+        reply: &Outbox,
+    ) -> Option<Value> {
+        let Value::Array(batch) = message else {
+            return self.handle_message(session, message, reply).await;
+        };
+        
+        if batch.is_empty() {
+            return Some(Self::error_response(
+                None,
+                &McpImageError::InvalidRequest("empty batch".to_string()),
+            ));
+        }
+        
+        tracing::info!("Handling batch of {} messages", batch.len());
+        let responses: Vec<Value> = join_all(
+            batch.into_iter().map(|message| self.handle_message(session, message, reply)),
+        )
+        .await
+        .into_iter()
+        .flatten()
+        .collect();
+        
+        if responses.is_empty() {
+            None
+        } else {
+            Some(Value::Array(responses))
+        }
+    }
+    
+    /// Handle a single JSON-RPC message, returning the response to write.
+    ///
+    /// Notifications (messages without an `id`) are dispatched but never
+    /// produce a response, so `None` is returned for them. Messages the
+    /// request emits while running (progress) are queued on `reply`, which
+    /// may differ from the session outbox (e.g. a per-request HTTP stream).
+    pub(crate) async fn handle_message(
+        &self,
+        session: &Session,
+        message: Value,
+        reply: &Outbox,
+    ) -> Option<Value> {
+        // The span routes log events raised while handling the message to
+        // this session only
+        let span = tracing::info_span!("request", session = session.id());
+        self.dispatch_message(session, message, reply).instrument(span).await
+    }
+    
+    async fn dispatch_message(
+        &self,
+        session: &Session,
+        message: Value,
+        reply: &Outbox,
+    ) -> Option<Value> {
         // Extract basic JSON-RPC fields
         let id = message.get("id").cloned();
         let method = message.get("method").and_then(|m| m.as_str());
@@ -79,97 +281,218 @@ impl McpImageServer {
         
         // Validate JSON-RPC 2.0
         if message.get("jsonrpc").and_then(|v| v.as_str()) != Some("2.0") {
-            return json!({
-                "jsonrpc": "2.0",
-                "id": id,
-                "error": {
-                    "code": -32600,
-                    "message": "Invalid Request",
-                    "data": "Missing or invalid jsonrpc version"
-                }
-            });
+            return Some(Self::error_response(
+                id,
+                &McpImageError::InvalidRequest("missing or invalid jsonrpc version".to_string()),
+            ));
         }
         
-        // ID must be present and not null
-        if id.is_none() || id == Some(Value::Null) {
-            return json!({
-                "jsonrpc": "2.0",
-                "id": null,
-                "error": {
-                    "code": -32600,
-                    "message": "Invalid Request",
-                    "data": "Missing or null id"
-                }
-            });
+        // A message without an id is a notification
+        if id.is_none() {
+            match method {
+                Some(method) => self.handle_notification(session, method, params).await,
+                None => tracing::warn!("Ignoring notification without method"),
+            }
+            return None;
+        }
+        
+        // Responses to server-initiated requests carry no method; we never
+        // send requests, so there is nothing to correlate them with
+        if method.is_none() && (message.get("result").is_some() || message.get("error").is_some()) {
+            tracing::debug!("Ignoring unexpected response message: {}", message);
+            return None;
         }
         
+        // ID must not be null
+        if id == Some(Value::Null) {
+            return Some(Self::error_response(None, &McpImageError::InvalidRequest("null id".to_string())));
+        }
+        
+        // Track the request so notifications/cancelled can reach it
+        let request_key = id.as_ref().map(Value::to_string).unwrap_or_default();
+        let cancel = session.register_request(&request_key);
+        let progress_token = params.get("_meta")
+            .and_then(|meta| meta.get("progressToken"))
+            .cloned();
+        let ctx = RequestContext::new(
+            cancel.clone(),
+            ProgressReporter::new(progress_token, reply.clone()),
+        );
+        
         let result = match method {
+            // Only the handshake and pings are allowed before initialize
+            Some(m) if m != "initialize" && m != "ping"
+                && session.lifecycle_state() == LifecycleState::Uninitialized =>
+            {
+                tracing::warn!("Rejecting {} before initialization", m);
+                Err(McpImageError::NotInitialized(format!(
+                    "{} received before initialize",
+                    m
+                )))
+            }
             Some("initialize") => {
                 tracing::info!("Handling initialize request");
-                self.handle_initialize(params).await
+                self.handle_initialize(session, params).await
+            }
+            Some("ping") => {
+                tracing::debug!("Handling ping request");
+                Ok(json!({}))
+            }
+            Some("logging/setLevel") => {
+                tracing::info!("Handling logging/setLevel request");
+                self.handle_set_level(session, params).await
             }
             Some("tools/list") => {
                 tracing::info!("Handling tools/list request");
-                self.handle_tools_list().await
+                self.handle_tools_list(session, params).await
             }
             Some("tools/call") => {
                 tracing::info!("Handling tools/call request");
-                self.handle_tool_call(params).await
+                self.handle_tool_call(session, params, &ctx).await
+            }
+            Some("resources/list") => {
+                tracing::info!("Handling resources/list request");
+                self.handle_resources_list(params).await
+            }
+            Some("resources/read") => {
+                tracing::info!("Handling resources/read request");
+                self.handle_resources_read(params).await
+            }
+            Some("resources/subscribe") => {
+                tracing::info!("Handling resources/subscribe request");
+                self.handle_resources_subscribe(session, params).await
+            }
+            Some("resources/unsubscribe") => {
+                tracing::info!("Handling resources/unsubscribe request");
+                self.handle_resources_unsubscribe(session, params).await
+            }
+            Some("resources/templates/list") => {
+                tracing::info!("Handling resources/templates/list request");
+                Ok(self.resources.templates())
+            }
+            Some("prompts/list") => {
+                tracing::info!("Handling prompts/list request");
+                Ok(self.prompts.list())
+            }
+            Some("prompts/get") => {
+                tracing::info!("Handling prompts/get request");
+                self.handle_prompts_get(params).await
             }
             Some(m) => {
                 tracing::warn!("Unknown method requested: {}", m);
-                Err(McpImageError::Mcp(format!(
-                    "Method not found: {}",
-                    m
-                )))
+                Err(McpImageError::MethodNotFound(m.to_string()))
             }
             None => {
                 tracing::warn!("Request missing method field");
-                Err(McpImageError::Mcp("Missing method".to_string()))
+                Err(McpImageError::InvalidRequest("missing method".to_string()))
             }
         };
         
+        session.unregister_request(&request_key);
+        
+        // A cancelled request must not be answered
+        if cancel.is_cancelled() {
+            tracing::info!("Request {} was cancelled, suppressing response", request_key);
+            return None;
+        }
+        
         // Build JSON-RPC response
         match result {
             Ok(result) => {
                 tracing::debug!("Request succeeded");
-                json!({
+                Some(json!({
                     "jsonrpc": "2.0",
                     "id": id,
                     "result": result
-                })
+                }))
             }
             Err(e) => {
                 tracing::error!("Request failed: {}", e);
-                let (code, message) = match &e {
-                    McpImageError::Mcp(msg) if msg.contains("Method not found") => {
-                        (-32601, "Method not found")
+                Some(Self::error_response(id, &e))
+            }
+        }
+    }
+    
+    /// JSON-RPC error response; a missing id is sent as null
+    fn error_response(id: Option<Value>, error: &McpImageError) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": error.to_jsonrpc_error()
+        })
+    }
+    
+    /// Handle a JSON-RPC notification. Errors are only logged since
+    /// notifications must never be answered.
+    async fn handle_notification(&self, session: &Session, method: &str, params: Value) {
+        match method {
+            "notifications/initialized" => {
+                // Only an answered initialize can be confirmed; anything
+                // else would skip the handshake or redo it
+                match session.lifecycle_state() {
+                    LifecycleState::Initializing => {
+                        tracing::info!("Client confirmed initialization");
+                        session.set_lifecycle_state(LifecycleState::Initialized);
                     }
-                    McpImageError::Mcp(msg) if msg.contains("Invalid params") => {
-                        (-32602, "Invalid params")
+                    LifecycleState::Uninitialized => {
+                        tracing::warn!("Ignoring initialized notification before initialize request");
                     }
-                    _ => (-32603, "Internal error")
-                };
-                
-                json!({
-                    "jsonrpc": "2.0",
-                    "id": id,
-                    "error": {
-                        "code": code,
-                        "message": message,
-                        "data": e.to_string()
+                    LifecycleState::Initialized => {
+                        tracing::debug!("Ignoring repeated initialized notification");
                     }
-                })
+                }
+            }
+            "notifications/cancelled" => {
+                let request_id = params.get("requestId").cloned().unwrap_or(Value::Null);
+                let reason = params.get("reason").and_then(|r| r.as_str()).unwrap_or("no reason given");
+                tracing::info!("Client cancelled request {}: {}", request_id, reason);
+                
+                if !session.cancel_request(&request_id.to_string()) {
+                    // Already finished or never seen - nothing to do
+                    tracing::debug!("No in-flight request {} to cancel", request_id);
+                }
+            }
+            m => {
+                tracing::debug!("Ignoring unknown notification: {}", m);
             }
         }
     }
     
-    async fn handle_initialize(&self, _params: Value) -> Result<Value> {
+    async fn handle_initialize(&self, session: &Session, params: Value) -> Result<Value> {
+        if session.lifecycle_state() != LifecycleState::Uninitialized {
+            return Err(McpImageError::InvalidRequest(
+                "session is already initialized".to_string()
+            ));
+        }
+        
+        let requested = params.get("protocolVersion").and_then(|v| v.as_str());
+        let version = ProtocolVersion::negotiate(requested);
+        tracing::info!(
+            "Negotiated protocol version {} (client requested {})",
+            version,
+            requested.unwrap_or("none")
+        );
+        
+        session.set_client(ClientInfo {
+            protocol_version: version,
+            capabilities: params.get("capabilities").cloned().unwrap_or_else(|| json!({})),
+            info: params.get("clientInfo").cloned().unwrap_or(Value::Null),
+        });
+        session.set_lifecycle_state(LifecycleState::Initializing);
+        
         // Return capabilities according to spec
         Ok(json!({
-            "protocolVersion": "2024-11-05",
+            "protocolVersion": version.as_str(),
             "capabilities": {
-                "tools": {}
+                "tools": {
+                    "listChanged": true
+                },
+                "resources": {
+                    "subscribe": true,
+                    "listChanged": true
+                },
+                "prompts": {},
+                "logging": {}
             },
             "serverInfo": {
                 "name": env!("CARGO_PKG_NAME"),
@@ -178,21 +501,85 @@ impl McpImageServer {
         }))
     }
     
-    async fn handle_tools_list(&self) -> Result<Value> {
-        Ok(json!({
-            "tools": [
-                ImageHandler::get_convert_tool_schema(),
-                ImageHandler::get_check_tool_schema()
-            ]
-        }))
+    async fn handle_resources_list(&self, params: Value) -> Result<Value> {
+        let cursor = params.get("cursor").and_then(|c| c.as_str());
+        self.resources.list(cursor).await
+    }
+    
+    async fn handle_resources_read(&self, params: Value) -> Result<Value> {
+        self.resources.read(Self::resource_uri(&params)?).await
     }
     
-    async fn handle_tool_call(&self, params: Value) -> Result<Value> {
+    async fn handle_resources_subscribe(&self, session: &Session, params: Value) -> Result<Value> {
+        self.resources.subscribe(session, Self::resource_uri(&params)?)?;
+        Ok(json!({}))
+    }
+    
+    async fn handle_resources_unsubscribe(&self, session: &Session, params: Value) -> Result<Value> {
+        let uri = Self::resource_uri(&params)?;
+        if !session.unsubscribe_resource(uri) {
+            tracing::debug!("Unsubscribe for {} which was not subscribed", uri);
+        }
+        Ok(json!({}))
+    }
+    
+    fn resource_uri(params: &Value) -> Result<&str> {
+        params.get("uri")
+            .and_then(|u| u.as_str())
+            .ok_or_else(|| McpImageError::InvalidParams("missing resource uri".to_string()))
+    }
+    
+    async fn handle_prompts_get(&self, params: Value) -> Result<Value> {
+        let name = params.get("name")
+            .and_then(|n| n.as_str())
+            .ok_or_else(|| McpImageError::InvalidParams("missing prompt name".to_string()))?;
+        let arguments = match params.get("arguments") {
+            Some(Value::Object(arguments)) => arguments.clone(),
+            Some(Value::Null) | None => serde_json::Map::new(),
+            Some(_) => {
+                return Err(McpImageError::InvalidParams(
+                    "prompt arguments must be an object".to_string(),
+                ))
+            }
+        };
+        self.prompts.get(name, &arguments)
+    }
+    
+    async fn handle_set_level(&self, session: &Session, params: Value) -> Result<Value> {
+        let level = params.get("level")
+            .and_then(|l| l.as_str())
+            .ok_or_else(|| McpImageError::InvalidParams("missing level".to_string()))?;
+        let level: LogLevel = level.parse().map_err(McpImageError::InvalidParams)?;
+        
+        session.set_log_level(level);
+        tracing::info!("Client log level set to {}", level);
+        Ok(json!({}))
+    }
+    
+    async fn handle_tools_list(&self, session: &Session, params: Value) -> Result<Value> {
+        let cursor = params.get("cursor").and_then(|c| c.as_str());
+        let mut result = self.tools.list(cursor)?;
+        
+        // Output schemas arrived with 2025-06-18
+        if !session.protocol_version().supports_structured_output() {
+            if let Some(tools) = result["tools"].as_array_mut() {
+                for tool in tools {
+                    if let Some(tool) = tool.as_object_mut() {
+                        tool.remove("outputSchema");
+                    }
+                }
+            }
+        }
+        
+        Ok(result)
+    }
+    
+    async fn handle_tool_call(&self, session: &Session, params: Value, ctx: &RequestContext) -> Result<Value> {
         let name = params.get("name")
             .and_then(|n| n.as_str())
             .ok_or_else(|| {
                 tracing::error!("tools/call missing 'name' parameter");
-                McpImageError::Mcp("Invalid params: missing tool name".to_string())
+                McpImageError::InvalidParams("missing tool name".to_string())
             })?;
         
         let arguments = params.get("arguments")
@@ -204,11 +591,28 @@ impl McpImageServer {
         
         tracing::info!("Calling tool '{}' with arguments: {}", name, arguments);
         
-        match self.handler.handle_tool_call(name, arguments).await {
-            Ok(result) => {
+        match self.tools.call(name, arguments, ctx).await {
+            Ok(mut result) => {
                 tracing::info!("Tool '{}' succeeded", name);
+                if !session.protocol_version().supports_structured_output() {
+                    if let Some(result) = result.as_object_mut() {
+                        result.remove("structuredContent");
+                    }
+                }
                 Ok(result)
             }
+            // The model gets to see why its tool call failed
+            Err(e) if e.is_tool_error() => {
+                tracing::warn!("Tool '{}' failed: {}", name, e);
+                Ok(json!({
+                    "content": [{
+                        "type": "text",
+                        "text": e.to_string()
+                    }],
+                    "isError": true,
+                    "_meta": { "error": e.data() }
+                }))
+            }
             Err(e) => {
                 tracing::error!("Tool '{}' failed: {}", name, e);
                 Err(e)
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+
+use crate::logging::LogLevel;
+use crate::transport::Outbox;
+
+/// Lifecycle of the MCP session, driven by the initialize handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LifecycleState {
+    /// No initialize request received yet
+    Uninitialized,
+    /// initialize answered, waiting for notifications/initialized
+    Initializing,
+    /// Client confirmed initialization, normal operation
+    Initialized,
+}
+
+/// MCP protocol revisions this server can speak, oldest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProtocolVersion {
+    V2024_11_05,
+    V2025_03_26,
+    V2025_06_18,
+}
+
+impl ProtocolVersion {
+    /// Every supported revision, newest first
+    pub const SUPPORTED: [ProtocolVersion; 3] = [
+        ProtocolVersion::V2025_06_18,
+        ProtocolVersion::V2025_03_26,
+        ProtocolVersion::V2024_11_05,
+    ];
+    
+    /// The newest revision, offered when the client asks for one we don't know
+    pub const LATEST: ProtocolVersion = ProtocolVersion::V2025_06_18;
+    
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProtocolVersion::V2024_11_05 => "2024-11-05",
+            ProtocolVersion::V2025_03_26 => "2025-03-26",
+            ProtocolVersion::V2025_06_18 => "2025-06-18",
+        }
+    }
+    
+    pub fn parse(version: &str) -> Option<Self> {
+        Self::SUPPORTED.into_iter().find(|v| v.as_str() == version)
+    }
+    
+    /// Pick the version to answer an initialize request with: the client's
+    /// if we support it, otherwise our latest and let the client decide
+    pub fn negotiate(requested: Option<&str>) -> Self {
+        match requested {
+            Some(requested) => Self::parse(requested).unwrap_or(Self::LATEST),
+            // Clients that predate negotiation never sent a version
+            None => ProtocolVersion::V2024_11_05,
+        }
+    }
+    
+    /// `outputSchema` on tools and `structuredContent` in results
+    pub fn supports_structured_output(self) -> bool {
+        self >= ProtocolVersion::V2025_06_18
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// What the client told us about itself during initialize
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub protocol_version: ProtocolVersion,
+    /// The `capabilities` object from the initialize request
+    pub capabilities: Value,
+    /// The `clientInfo` object from the initialize request, if any
+    pub info: Value,
+}
+
+/// State of one client connection.
+///
+/// The stdio transport has exactly one session; the HTTP transport keeps one
+/// per `Mcp-Session-Id`, all sharing the same `McpImageServer`.
+#[derive(Debug)]
+pub struct Session {
+    /// Process-unique id, used to route log events to their session
+    id: u64,
+    state: RwLock<LifecycleState>,
+    /// Negotiated version and client capabilities, set by initialize
+    client: RwLock<Option<ClientInfo>>,
+    /// Minimum level forwarded as `notifications/message`
+    log_level: RwLock<LogLevel>,
+    /// Cancellation tokens of requests being processed, keyed by JSON id
+    in_flight: Mutex<HashMap<String, CancellationToken>>,
+    /// Resources the client subscribed to: URI as sent by the client, and
+    /// the file it resolved to
+    subscriptions: Mutex<HashMap<String, PathBuf>>,
+    /// Sink for server-initiated messages not tied to a request
+    outbox: Outbox,
+}
+
+impl Session {
+    pub fn new(outbox: Outbox) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            state: RwLock::new(LifecycleState::Uninitialized),
+            client: RwLock::new(None),
+            log_level: RwLock::new(LogLevel::DEFAULT),
+            in_flight: Mutex::new(HashMap::new()),
+            subscriptions: Mutex::new(HashMap::new()),
+            outbox,
+        }
+    }
+    
+    /// Process-unique session id
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    
+    /// Current lifecycle state of the session
+    pub fn lifecycle_state(&self) -> LifecycleState {
+        *self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+    
+    /// Whether the client has completed the initialize handshake
+    pub fn is_initialized(&self) -> bool {
+        self.lifecycle_state() == LifecycleState::Initialized
+    }
+    
+    pub(crate) fn set_lifecycle_state(&self, state: LifecycleState) {
+        *self.state.write().unwrap_or_else(|e| e.into_inner()) = state;
+    }
+    
+    /// Client details recorded during initialize
+    pub fn client(&self) -> Option<ClientInfo> {
+        self.client.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+    
+    pub(crate) fn set_client(&self, client: ClientInfo) {
+        *self.client.write().unwrap_or_else(|e| e.into_inner()) = Some(client);
+    }
+    
+    /// Negotiated protocol version; the oldest revision before initialize
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.client
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|c| c.protocol_version)
+            .unwrap_or(ProtocolVersion::V2024_11_05)
+    }
+    
+    /// Minimum level of log events forwarded to the client
+    pub fn log_level(&self) -> LogLevel {
+        *self.log_level.read().unwrap_or_else(|e| e.into_inner())
+    }
+    
+    pub(crate) fn set_log_level(&self, level: LogLevel) {
+        *self.log_level.write().unwrap_or_else(|e| e.into_inner()) = level;
+    }
+    
+    /// Outbox for notifications that are not part of a request's reply
+    pub fn outbox(&self) -> &Outbox {
+        &self.outbox
+    }
+    
+    /// Create the cancellation token for an in-flight request
+    pub(crate) fn register_request(&self, key: &str) -> CancellationToken {
+        let token = CancellationToken::new();
+        self.in_flight.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.to_string(), token.clone());
+        token
+    }
+    
+    pub(crate) fn unregister_request(&self, key: &str) {
+        self.in_flight.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(key);
+    }
+    
+    /// Cancel an in-flight request. Returns false if it is not running.
+    pub(crate) fn cancel_request(&self, key: &str) -> bool {
+        let token = self.in_flight.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(key)
+            .cloned();
+        match token {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// Whether any request is still being handled
+    pub(crate) fn has_requests_in_flight(&self) -> bool {
+        !self.in_flight.lock().unwrap_or_else(|e| e.into_inner()).is_empty()
+    }
+    
+    /// Cancel every in-flight request, e.g. when the session is terminated
+    pub(crate) fn cancel_all(&self) {
+        for token in self.in_flight.lock().unwrap_or_else(|e| e.into_inner()).values() {
+            token.cancel();
+        }
+    }
+    
+    pub(crate) fn subscribe_resource(&self, uri: &str, path: PathBuf) {
+        self.subscriptions.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(uri.to_string(), path);
+    }
+    
+    /// Drop a subscription. Returns false if the URI was not subscribed.
+    pub(crate) fn unsubscribe_resource(&self, uri: &str) -> bool {
+        self.subscriptions.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(uri)
+            .is_some()
+    }
+    
+    /// URIs this session subscribed to that refer to `path`
+    pub fn subscribed_uris(&self, path: &Path) -> Vec<String> {
+        self.subscriptions.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(_, p)| p.as_path() == path)
+            .map(|(uri, _)| uri.clone())
+            .collect()
+    }
+}
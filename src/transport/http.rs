@@ -0,0 +1,546 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
+use tokio_stream::StreamExt;
+
+use crate::{Result, McpImageError, McpImageServer};
+use crate::logging::WIRE_TARGET;
+use crate::session::{ProtocolVersion, Session};
+use super::Outbox;
+
+/// Header carrying the session id assigned on initialize
+pub const SESSION_HEADER: &str = "mcp-session-id";
+
+/// Header naming the negotiated protocol version on every request after
+/// initialize (2025-06-18)
+pub const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+
+/// The single MCP endpoint path
+pub const MCP_PATH: &str = "/mcp";
+
+/// How long a session may sit unused before it is dropped
+pub const DEFAULT_SESSION_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Notifications buffered for a GET stream whose client is slow to read
+const STREAM_BUFFER: usize = 64;
+
+/// Streamable HTTP transport (MCP 2025-03-26).
+///
+/// Clients POST JSON-RPC messages to `/mcp`; requests are answered with
+/// `application/json`, or with an SSE stream when the client accepts
+/// `text/event-stream` and asked for progress. A GET on `/mcp` opens the
+/// session's stream for server-initiated notifications, and DELETE ends
+/// the session. Sessions are identified by the `Mcp-Session-Id` header.
+///
+/// Notifications sent while no GET stream is open are dropped. Sessions
+/// left idle for the session timeout, with no stream and no running
+/// request, are ended as if deleted.
+///
+/// With a bearer token set, every request must carry it in an
+/// `Authorization: Bearer` header.
+pub struct HttpTransport {
+    listener: TcpListener,
+    allowed_origins: Vec<String>,
+    session_timeout: Duration,
+    bearer_token: Option<String>,
+}
+
+impl HttpTransport {
+    /// Bind the listener. Use port 0 to let the OS pick a free port.
+    pub async fn bind(addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        Ok(Self {
+            listener,
+            allowed_origins: Vec::new(),
+            session_timeout: DEFAULT_SESSION_TIMEOUT,
+            bearer_token: None,
+        })
+    }
+    
+    /// Accept browser requests from these `Origin` values in addition to
+    /// localhost origins
+    pub fn with_allowed_origins(mut self, origins: Vec<String>) -> Self {
+        self.allowed_origins = origins;
+        self
+    }
+    
+    /// End sessions unused for this long; zero keeps them until DELETE
+    pub fn with_session_timeout(mut self, timeout: Duration) -> Self {
+        self.session_timeout = timeout;
+        self
+    }
+    
+    /// Require `Authorization: Bearer <token>` on every request
+    pub fn with_bearer_token(mut self, token: Option<String>) -> Self {
+        self.bearer_token = token;
+        self
+    }
+    
+    /// Address the transport is listening on
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+    
+    /// Serve sessions until the process receives Ctrl-C
+    pub async fn serve(self, server: McpImageServer) -> Result<()> {
+        self.serve_with_shutdown(server, async {
+            if let Err(e) = tokio::signal::ctrl_c().await {
+                tracing::error!("Failed to listen for shutdown signal: {}", e);
+                std::future::pending::<()>().await;
+            }
+        })
+        .await
+    }
+    
+    /// Serve sessions until `shutdown` completes
+    pub async fn serve_with_shutdown<F>(self, server: McpImageServer, shutdown: F) -> Result<()>
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let addr = self.local_addr()?;
+        tracing::info!("Starting MCP Image Server on http://{}{}", addr, MCP_PATH);
+        if self.bearer_token.is_none() && !addr.ip().is_loopback() {
+            // The tools read and write any path the server user can
+            tracing::warn!("HTTP transport on {} accepts unauthenticated clients from the network", addr);
+        }
+        
+        let state = Arc::new(AppState {
+            server: Arc::new(server),
+            sessions: Mutex::new(HashMap::new()),
+            allowed_origins: self.allowed_origins,
+            bearer_token: self.bearer_token,
+        });
+        
+        let app = Router::new()
+            .route(MCP_PATH, post(handle_post).get(handle_get).delete(handle_delete))
+            .with_state(state.clone());
+        
+        let expiry = (!self.session_timeout.is_zero())
+            .then(|| tokio::spawn(expire_sessions(state.clone(), self.session_timeout)));
+        
+        axum::serve(self.listener, app)
+            .with_graceful_shutdown(shutdown)
+            .await?;
+        
+        if let Some(expiry) = expiry {
+            expiry.abort();
+        }
+        
+        // Stop whatever is still running for sessions that never said goodbye
+        for session in state.sessions.lock().unwrap_or_else(|e| e.into_inner()).values() {
+            session.session.cancel_all();
+        }
+        
+        tracing::info!("HTTP server shut down");
+        Ok(())
+    }
+}
+
+/// Sender feeding the session's open GET stream, if any
+type StreamSlot = Arc<Mutex<Option<mpsc::Sender<Value>>>>;
+
+struct HttpSession {
+    session: Arc<Session>,
+    stream: StreamSlot,
+    last_active: Mutex<Instant>,
+}
+
+impl HttpSession {
+    fn new(session: Session, stream: StreamSlot) -> Self {
+        Self {
+            session: Arc::new(session),
+            stream,
+            last_active: Mutex::new(Instant::now()),
+        }
+    }
+    
+    fn stream_open(&self) -> bool {
+        self.stream
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .is_some_and(|sender| !sender.is_closed())
+    }
+    
+    fn touch(&self) {
+        *self.last_active.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+    
+    /// Time since the session was last used; an open stream or a running
+    /// request counts as use
+    fn idle_for(&self) -> Duration {
+        if self.stream_open() || self.session.has_requests_in_flight() {
+            self.touch();
+        }
+        self.last_active.lock().unwrap_or_else(|e| e.into_inner()).elapsed()
+    }
+}
+
+struct AppState {
+    server: Arc<McpImageServer>,
+    sessions: Mutex<HashMap<String, Arc<HttpSession>>>,
+    allowed_origins: Vec<String>,
+    bearer_token: Option<String>,
+}
+
+impl AppState {
+    fn session(&self, id: &str) -> Option<Arc<HttpSession>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner()).get(id).cloned()
+    }
+    
+    /// Reject browser origins that are neither local nor configured, which
+    /// protects local servers against DNS rebinding
+    fn origin_allowed(&self, headers: &HeaderMap) -> bool {
+        let Some(origin) = headers.get(header::ORIGIN).and_then(|o| o.to_str().ok()) else {
+            return true;
+        };
+        
+        if self.allowed_origins.iter().any(|allowed| allowed == origin) {
+            return true;
+        }
+        
+        let authority = origin.split_once("://").map(|(_, rest)| rest).unwrap_or(origin);
+        let host = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => host,
+            _ => authority,
+        };
+        matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+    }
+    
+    /// Whether the request carries the configured bearer token, if any.
+    /// The comparison takes the same time wherever the tokens differ.
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(expected) = &self.bearer_token else {
+            return true;
+        };
+        let Some(given) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+        given.len() == expected.len()
+            && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+    
+    /// The rejection for requests from a foreign origin or without the token
+    fn rejection(&self, headers: &HeaderMap) -> Option<Response> {
+        if !self.origin_allowed(headers) {
+            return Some(StatusCode::FORBIDDEN.into_response());
+        }
+        if !self.authorized(headers) {
+            return Some(
+                (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")]).into_response(),
+            );
+        }
+        None
+    }
+    
+    /// Resolve the session named by the request headers
+    fn require_session(
+        &self,
+        headers: &HeaderMap,
+    ) -> std::result::Result<(String, Arc<HttpSession>), SessionError> {
+        let id = headers
+            .get(SESSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .ok_or(SessionError::Missing)?;
+        let session = self.session(id).ok_or(SessionError::Unknown)?;
+        session.touch();
+        Ok((id.to_string(), session))
+    }
+}
+
+/// Why a request could not be tied to a session
+enum SessionError {
+    /// No `Mcp-Session-Id` header on a non-initialize request
+    Missing,
+    /// The session was terminated or never existed; the client must
+    /// initialize again
+    Unknown,
+}
+
+impl IntoResponse for SessionError {
+    fn into_response(self) -> Response {
+        match self {
+            SessionError::Missing => {
+                invalid_request(StatusCode::BAD_REQUEST, "missing Mcp-Session-Id header")
+            }
+            SessionError::Unknown => {
+                invalid_request(StatusCode::NOT_FOUND, "unknown or terminated session")
+            }
+        }
+    }
+}
+
+async fn handle_post(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Some(rejected) = state.rejection(&headers) {
+        return rejected;
+    }
+    
+    if !protocol_header_valid(&headers) {
+        return unsupported_protocol_version();
+    }
+    
+    let message: Value = match serde_json::from_slice(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::warn!("Failed to parse HTTP request body: {}", e);
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": {
+                        "code": -32700,
+                        "message": "Parse error",
+                        "data": e.to_string()
+                    }
+                })),
+            )
+                .into_response();
+        }
+    };
+    tracing::debug!(target: WIRE_TARGET, "Received message: {}", message);
+    
+    // A batch counts as a request if any of its elements is one
+    let is_request = match &message {
+        Value::Array(batch) => batch.is_empty() || batch.iter().any(is_request_message),
+        message => is_request_message(message),
+    };
+    let is_initialize = message.get("method").and_then(|m| m.as_str()) == Some("initialize")
+        && message.get("id").is_some();
+    
+    // initialize starts a new session; everything else must name one
+    let (session_id, http_session) = if is_initialize {
+        let (outbox, notifications) = Outbox::channel();
+        let stream = StreamSlot::default();
+        tokio::spawn(forward_notifications(notifications, stream.clone()));
+        let session = HttpSession::new(Session::new(outbox), stream);
+        (uuid::Uuid::new_v4().to_string(), Arc::new(session))
+    } else {
+        match state.require_session(&headers) {
+            Ok(found) => found,
+            Err(e) => return e.into_response(),
+        }
+    };
+    
+    // Notifications and responses are accepted without a body
+    if !is_request {
+        let session = &http_session.session;
+        state.server.handle_incoming(session, message, session.outbox()).await;
+        return StatusCode::ACCEPTED.into_response();
+    }
+    
+    let wants_stream = accepts(&headers, "text/event-stream")
+        && message
+            .get("params")
+            .and_then(|p| p.get("_meta"))
+            .and_then(|m| m.get("progressToken"))
+            .is_some();
+    
+    // Run the request on its own task so a dropped connection doesn't
+    // abort the conversion; everything it emits lands on `replies`
+    let (reply, mut replies) = Outbox::channel();
+    {
+        let server = state.server.clone();
+        let session = http_session.session.clone();
+        tokio::spawn(async move {
+            if let Some(response) = server.handle_incoming(&session, message, &reply).await {
+                reply.send(response);
+            }
+        });
+    }
+    
+    let mut handshake_failed = false;
+    let mut response = if wants_stream {
+        let events = UnboundedReceiverStream::new(replies).map(to_event);
+        Sse::new(events).into_response()
+    } else {
+        // Wait for the response, passing request notifications on to the
+        // session stream
+        let mut result = None;
+        while let Some(outgoing) = replies.recv().await {
+            if outgoing.get("method").is_some() {
+                http_session.session.outbox().send(outgoing);
+            } else {
+                result = Some(outgoing);
+                break;
+            }
+        }
+        
+        match result {
+            Some(result) => {
+                handshake_failed = result.get("error").is_some();
+                (StatusCode::OK, Json(result)).into_response()
+            }
+            // Cancelled requests get no response
+            None => StatusCode::ACCEPTED.into_response(),
+        }
+    };
+    
+    // A failed handshake leaves no session behind
+    if is_initialize && !handshake_failed {
+        tracing::info!("Created HTTP session {}", session_id);
+        state.server.register_session(&http_session.session);
+        state
+            .sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(session_id.clone(), http_session);
+        if let Ok(value) = HeaderValue::from_str(&session_id) {
+            response.headers_mut().insert(SESSION_HEADER, value);
+        }
+    }
+    
+    response
+}
+
+async fn handle_get(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if let Some(rejected) = state.rejection(&headers) {
+        return rejected;
+    }
+    if !protocol_header_valid(&headers) {
+        return unsupported_protocol_version();
+    }
+    if !accepts(&headers, "text/event-stream") {
+        return StatusCode::NOT_ACCEPTABLE.into_response();
+    }
+    
+    let http_session = match state.require_session(&headers) {
+        Ok((_, session)) => session,
+        Err(e) => return e.into_response(),
+    };
+    
+    // Each message must go out on exactly one stream, so only one GET
+    // stream per session may be open at a time
+    let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+    {
+        let mut slot = http_session.stream.lock().unwrap_or_else(|e| e.into_inner());
+        if slot.as_ref().is_some_and(|open| !open.is_closed()) {
+            return (StatusCode::CONFLICT, "Session stream already open").into_response();
+        }
+        *slot = Some(sender);
+    }
+    
+    Sse::new(ReceiverStream::new(receiver).map(to_event))
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+async fn handle_delete(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if let Some(rejected) = state.rejection(&headers) {
+        return rejected;
+    }
+    
+    let (id, http_session) = match state.require_session(&headers) {
+        Ok(found) => found,
+        Err(e) => return e.into_response(),
+    };
+    
+    state.sessions.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+    http_session.session.cancel_all();
+    tracing::info!("Terminated HTTP session {}", id);
+    StatusCode::OK.into_response()
+}
+
+/// Pass the session's notifications to its GET stream. With no stream
+/// open, or one whose client has stopped reading, they are dropped rather
+/// than queued. Ends when the session is dropped.
+async fn forward_notifications(mut notifications: mpsc::UnboundedReceiver<Value>, stream: StreamSlot) {
+    while let Some(message) = notifications.recv().await {
+        let sender = stream.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        if sender.is_none_or(|sender| sender.try_send(message).is_err()) {
+            // Logged on the wire target so dropped log notifications don't
+            // produce more of them
+            tracing::debug!(target: WIRE_TARGET, "Dropped notification: no stream is reading it");
+        }
+    }
+}
+
+/// End sessions left idle for `timeout`
+async fn expire_sessions(state: Arc<AppState>, timeout: Duration) {
+    let mut ticks = tokio::time::interval((timeout / 2).clamp(Duration::from_millis(1), Duration::from_secs(60)));
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticks.tick().await;
+        let expired: Vec<(String, Arc<HttpSession>)> = {
+            let mut sessions = state.sessions.lock().unwrap_or_else(|e| e.into_inner());
+            let ids: Vec<String> = sessions
+                .iter()
+                .filter(|(_, session)| session.idle_for() >= timeout)
+                .map(|(id, _)| id.clone())
+                .collect();
+            ids.into_iter()
+                .filter_map(|id| sessions.remove(&id).map(|session| (id, session)))
+                .collect()
+        };
+        for (id, http_session) in expired {
+            http_session.session.cancel_all();
+            tracing::info!("Expired idle HTTP session {}", id);
+        }
+    }
+}
+
+fn to_event(message: Value) -> std::result::Result<Event, Infallible> {
+    tracing::debug!(target: WIRE_TARGET, "Sending message: {}", message);
+    Ok(Event::default().event("message").data(message.to_string()))
+}
+
+/// Clients on 2025-06-18 and later name the negotiated revision in the
+/// `MCP-Protocol-Version` header; an unknown value is a bad request
+fn protocol_header_valid(headers: &HeaderMap) -> bool {
+    match headers.get(PROTOCOL_VERSION_HEADER).map(|v| v.to_str()) {
+        None => true,
+        Some(Ok(version)) => ProtocolVersion::parse(version).is_some(),
+        Some(Err(_)) => false,
+    }
+}
+
+fn unsupported_protocol_version() -> Response {
+    invalid_request(StatusCode::BAD_REQUEST, "unsupported MCP-Protocol-Version header")
+}
+
+fn is_request_message(message: &Value) -> bool {
+    message.get("id").is_some() && message.get("method").is_some()
+}
+
+fn accepts(headers: &HeaderMap, mime: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.contains(mime) || v.contains("*/*"))
+}
+
+/// An `McpImageError::InvalidRequest` response with no request id
+fn invalid_request(status: StatusCode, detail: &str) -> Response {
+    let error = McpImageError::InvalidRequest(detail.to_string());
+    (
+        status,
+        Json(json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": error.to_jsonrpc_error()
+        })),
+    )
+        .into_response()
+}
@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::Mutex;
+
+use crate::{Result, McpImageError};
+use super::Transport;
+
+/// Newline-delimited JSON-RPC over any async byte stream.
+///
+/// Reading and writing are guarded independently so a reader task and a
+/// writer task can share one transport without blocking each other.
+pub struct LineTransport<R, W> {
+    reader: Mutex<BufReader<R>>,
+    writer: Mutex<W>,
+}
+
+impl<R, W> LineTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    pub fn from_io(reader: R, writer: W) -> Self {
+        Self {
+            reader: Mutex::new(BufReader::new(reader)),
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+#[async_trait]
+impl<R, W> Transport for LineTransport<R, W>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    async fn read_message(&self) -> Option<Value> {
+        let mut reader = self.reader.lock().await;
+        let mut line = String::new();
+        
+        match reader.read_line(&mut line).await {
+            Ok(0) => None, // EOF
+            Ok(_) => {
+                let trimmed = line.trim();
+                if trimmed.is_empty() {
+                    return Some(Value::Null); // Signal empty line
+                }
+                
+                match serde_json::from_str::<Value>(trimmed) {
+                    Ok(value) => Some(value),
+                    Err(e) => {
+                        tracing::warn!("Failed to parse JSON: {}", e);
+                        tracing::warn!("Invalid line: {}", trimmed);
+                        // Return parse error as JSON-RPC error
+                        Some(json!({
+                            "jsonrpc": "2.0",
+                            "error": {
+                                "code": -32700,
+                                "message": "Parse error",
+                                "data": e.to_string()
+                            },
+                            "id": null
+                        }))
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to read message: {}", e);
+                None
+            }
+        }
+    }
+    
+    async fn write_message(&self, message: &Value) -> Result<()> {
+        let mut writer = self.writer.lock().await;
+        
+        // Convert to JSON string (single line) followed by a newline
+        let mut json = serde_json::to_string(message)
+            .map_err(|e| McpImageError::Mcp(format!("Failed to serialize JSON: {}", e)))?;
+        json.push('\n');
+        
+        // Write the whole line at once so messages never interleave
+        writer.write_all(json.as_bytes()).await
+            .map_err(|e| McpImageError::Mcp(format!("Failed to write message: {}", e)))?;
+        
+        // Flush to ensure the message is sent immediately
+        writer.flush().await
+            .map_err(|e| McpImageError::Mcp(format!("Failed to flush message: {}", e)))?;
+        
+        Ok(())
+    }
+}
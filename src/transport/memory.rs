@@ -0,0 +1,20 @@
+use tokio::io::{self, DuplexStream, ReadHalf, WriteHalf};
+
+use super::LineTransport;
+
+/// Buffer size of each direction of an in-memory pipe
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// In-memory transport, mainly for driving the server from tests
+pub type MemoryTransport = LineTransport<ReadHalf<DuplexStream>, WriteHalf<DuplexStream>>;
+
+impl MemoryTransport {
+    /// Create two connected transports: give one to the server and use the
+    /// other as the client end
+    pub fn pair() -> (Self, Self) {
+        let (a, b) = io::duplex(PIPE_CAPACITY);
+        let (a_read, a_write) = io::split(a);
+        let (b_read, b_write) = io::split(b);
+        (LineTransport::from_io(a_read, a_write), LineTransport::from_io(b_read, b_write))
+    }
+}
@@ -0,0 +1,65 @@
+mod http;
+mod line;
+mod memory;
+mod stdio;
+#[cfg(unix)]
+mod unix;
+
+pub use http::{HttpTransport, DEFAULT_SESSION_TIMEOUT};
+pub use line::LineTransport;
+pub use memory::MemoryTransport;
+pub use stdio::StdioTransport;
+#[cfg(unix)]
+pub use unix::UnixSocketTransport;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use crate::Result;
+
+/// A bidirectional channel of JSON-RPC messages serving a single session.
+///
+/// Both methods take `&self` so the server can read and write concurrently
+/// from separate tasks.
+#[async_trait]
+pub trait Transport: Send + Sync + 'static {
+    /// Read the next message. Returns `None` at end of stream,
+    /// `Some(Value::Null)` for a blank line, and a ready-made JSON-RPC
+    /// parse-error response for input that isn't valid JSON.
+    async fn read_message(&self) -> Option<Value>;
+    
+    /// Write one message
+    async fn write_message(&self, message: &Value) -> Result<()>;
+}
+
+/// Cloneable handle for queueing outgoing messages.
+///
+/// Every response and notification goes through a single writer task that
+/// drains the receiving end, so concurrent handlers never interleave lines.
+#[derive(Clone, Debug)]
+pub struct Outbox {
+    tx: mpsc::UnboundedSender<Value>,
+}
+
+impl Outbox {
+    /// Create an outbox and the receiver the writer task should drain
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<Value>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (Self { tx }, rx)
+    }
+    
+    /// Queue a message for writing. Returns false if the writer has stopped.
+    pub fn send(&self, message: Value) -> bool {
+        self.tx.send(message).is_ok()
+    }
+    
+    /// Queue a JSON-RPC notification
+    pub fn notify(&self, method: &str, params: Value) -> bool {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params
+        }))
+    }
+}
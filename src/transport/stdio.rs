@@ -0,0 +1,18 @@
+use tokio::io::{self, Stdin, Stdout};
+
+use super::LineTransport;
+
+/// Stdio transport: one client talking over the process's stdin/stdout
+pub type StdioTransport = LineTransport<Stdin, Stdout>;
+
+impl Default for StdioTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StdioTransport {
+    pub fn new() -> Self {
+        LineTransport::from_io(io::stdin(), io::stdout())
+    }
+}
@@ -0,0 +1,22 @@
+use std::path::Path;
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::UnixStream;
+
+use crate::Result;
+use super::LineTransport;
+
+/// Newline-delimited JSON-RPC over a Unix domain socket connection
+pub type UnixSocketTransport = LineTransport<OwnedReadHalf, OwnedWriteHalf>;
+
+impl UnixSocketTransport {
+    /// Wrap an accepted or connected socket
+    pub fn from_stream(stream: UnixStream) -> Self {
+        let (read, write) = stream.into_split();
+        LineTransport::from_io(read, write)
+    }
+    
+    /// Connect to a server listening on `path`
+    pub async fn connect(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::from_stream(UnixStream::connect(path).await?))
+    }
+}
@@ -0,0 +1,91 @@
+//! Harness shared by the end-to-end tests. Each test binary uses a subset.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use mcp_imagemagick::transport::{MemoryTransport, Transport};
+use mcp_imagemagick::{McpImageServer, ServerConfig};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+
+/// Serve a fresh server over an in-memory transport, returning the client end
+pub fn start_server(config: ServerConfig) -> (MemoryTransport, JoinHandle<mcp_imagemagick::Result<()>>) {
+    let (server_end, client) = MemoryTransport::pair();
+    let server = Arc::new(McpImageServer::with_config(config));
+    (client, tokio::spawn(server.serve(server_end)))
+}
+
+pub async fn recv(client: &MemoryTransport) -> Value {
+    tokio::time::timeout(Duration::from_secs(5), client.read_message())
+        .await
+        .expect("timed out waiting for server")
+        .expect("server closed the connection")
+}
+
+pub async fn request(client: &MemoryTransport, message: Value) -> Value {
+    client.write_message(&message).await.unwrap();
+    recv(client).await
+}
+
+/// Initialize with empty params, which negotiates the oldest protocol version
+pub async fn initialize(client: &MemoryTransport) -> Value {
+    initialize_with(client, json!({})).await
+}
+
+/// Initialize at `version`, e.g. "2025-06-18" to get `structuredContent`
+pub async fn initialize_version(client: &MemoryTransport, version: &str) -> Value {
+    initialize_with(client, json!({"protocolVersion": version, "capabilities": {}})).await
+}
+
+async fn initialize_with(client: &MemoryTransport, params: Value) -> Value {
+    let response = request(client, json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": params
+    }))
+    .await;
+    client
+        .write_message(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+        .await
+        .unwrap();
+    response
+}
+
+/// Write an executable shell script to `path`
+pub fn write_script(path: &Path, script: &str) {
+    std::fs::write(path, script).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
+
+/// Shell utilities stub scripts may call; nothing else is left on `PATH`
+const STUB_UTILITIES: [&str; 3] = ["cat", "head", "sleep"];
+
+/// Replace `PATH` with a directory holding only `stubs` (name and script)
+/// and the utilities they use, so no converter installed on the host, e.g.
+/// a `convert7` that ImageMagickConverter would prefer, is ever run.
+///
+/// `PATH` is set once per test binary, before the first server starts;
+/// every caller in a binary must pass the same stubs.
+pub fn install_stubs(stubs: &[(&str, &str)]) {
+    static STUB_DIR: OnceLock<PathBuf> = OnceLock::new();
+    STUB_DIR.get_or_init(|| {
+        let dir = tempfile::tempdir().unwrap().keep();
+        for (name, script) in stubs {
+            write_script(&dir.join(name), script);
+        }
+        #[cfg(unix)]
+        for utility in STUB_UTILITIES {
+            let target = which::which(utility).unwrap_or_else(|e| panic!("{} not found: {}", utility, e));
+            std::os::unix::fs::symlink(target, dir.join(utility)).unwrap();
+        }
+        std::env::set_var("PATH", &dir);
+        dir
+    });
+}
@@ -0,0 +1,475 @@
+//! Conversion tools against stub `magick` and `darktable-cli` binaries that
+//! succeed and record their arguments
+
+mod common;
+
+use std::path::Path;
+use std::time::Duration;
+
+use mcp_imagemagick::transport::{MemoryTransport, Transport};
+use mcp_imagemagick::ServerConfig;
+use serde_json::{json, Value};
+
+use common::{initialize_version, install_stubs, recv, request};
+
+/// Make stub converters the only ones on `PATH`. Each writes its
+/// arguments, one per line, to `<output>.argv`, sources `<first
+/// argument>.stub` when a test leaves one there, and then writes a small
+/// output file.
+fn install_stub_converters() {
+    // The output is the last argument, after any coder prefix
+    install_stubs(&[
+        ("magick", concat!(
+            "#!/bin/sh\n",
+            "for arg; do out=$arg; done\n",
+            "out=${out#*:}\n",
+            "printf '%s\\n' \"$@\" > \"$out.argv\"\n",
+            "[ -f \"$1.stub\" ] && . \"$1.stub\"\n",
+            "echo image > \"$out\"\n",
+        )),
+        ("darktable-cli", concat!(
+            "#!/bin/sh\n",
+            "out=$2\n",
+            "printf '%s\\n' \"$@\" > \"$out.argv\"\n",
+            "[ -f \"$1.stub\" ] && . \"$1.stub\"\n",
+            "echo image > \"$out\"\n",
+        )),
+    ]);
+}
+
+/// A server with the stubs installed, initialized for `structuredContent`
+async fn start_server() -> MemoryTransport {
+    install_stub_converters();
+    let (client, _server) = common::start_server(ServerConfig::default());
+    initialize_version(&client, "2025-06-18").await;
+    client
+}
+
+async fn call_tool(client: &MemoryTransport, name: &str, arguments: Value) -> Value {
+    let response = request(client, json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": {"name": name, "arguments": arguments}
+    }))
+    .await;
+    assert_ne!(response["result"]["isError"], true, "{}", response);
+    response["result"].clone()
+}
+
+/// Arguments a stub was run with to write `output`
+fn recorded_argv(output: &Path) -> Vec<String> {
+    let mut argv = output.as_os_str().to_owned();
+    argv.push(".argv");
+    std::fs::read_to_string(&argv)
+        .unwrap_or_else(|e| panic!("no converter wrote {}: {}", output.display(), e))
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+/// Make the stub run `script` when its first argument is `path`
+fn stub_behaviour(path: &Path, script: &str) {
+    let mut stub = path.as_os_str().to_owned();
+    stub.push(".stub");
+    std::fs::write(stub, script).unwrap();
+}
+
+#[tokio::test]
+async fn failed_previews_are_reported_beside_the_result() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    let output = dir.path().join("out.webp");
+    std::fs::write(&input, b"png").unwrap();
+    // Previews are scaled from the output, so only the preview run fails
+    stub_behaviour(&output, "echo 'no thumbnail for you' >&2\nexit 1\n");
+    
+    let result = call_tool(&client, "convert_image", json!({
+        "input_path": input,
+        "output_path": output,
+        "return_preview": true
+    }))
+    .await;
+    
+    let content = result["content"].as_array().unwrap();
+    assert_eq!(content.len(), 2, "{}", result);
+    let note = content[1]["text"].as_str().unwrap();
+    assert!(note.starts_with("Preview unavailable: "), "{}", note);
+    assert!(note.contains("no thumbnail for you"), "{}", note);
+    let error = result["structuredContent"]["preview_error"].as_str().unwrap();
+    assert!(error.contains("no thumbnail for you"), "{}", error);
+    assert_eq!(result["structuredContent"]["success"], true);
+    assert!(output.exists());
+}
+
+#[tokio::test]
+async fn oversized_previews_are_rejected() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    let output = dir.path().join("out.webp");
+    std::fs::write(&input, b"png").unwrap();
+    // One byte over the 512 KiB cap
+    stub_behaviour(&output, "head -c 524289 /dev/zero > \"$out\"\nexit 0\n");
+    
+    let result = call_tool(&client, "convert_image", json!({
+        "input_path": input,
+        "output_path": output,
+        "return_preview": true
+    }))
+    .await;
+    
+    let content = result["content"].as_array().unwrap();
+    assert!(content.iter().all(|block| block["type"] == "text"), "{}", result);
+    let error = result["structuredContent"]["preview_error"].as_str().unwrap();
+    assert_eq!(error, "Conversion failed: Preview is 524289 bytes, over the 524288 byte limit");
+    assert_eq!(content[1]["text"], format!("Preview unavailable: {}", error));
+}
+
+#[tokio::test]
+async fn previews_within_the_cap_are_returned() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    let output = dir.path().join("out.webp");
+    std::fs::write(&input, b"png").unwrap();
+    stub_behaviour(&output, "head -c 524288 /dev/zero > \"$out\"\nexit 0\n");
+    
+    let result = call_tool(&client, "convert_image", json!({
+        "input_path": input,
+        "output_path": output,
+        "return_preview": true
+    }))
+    .await;
+    
+    let content = result["content"].as_array().unwrap();
+    assert_eq!(content[1]["type"], "image", "{}", result);
+    assert_eq!(content[1]["mimeType"], "image/webp");
+    assert!(result["structuredContent"]["preview_error"].is_null());
+    assert_eq!(recorded_argv(&output)[0], input.display().to_string());
+}
+
+#[tokio::test]
+async fn transform_runs_one_command_and_reports_printed_dimensions() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.jpg");
+    let output = dir.path().join("out.png");
+    std::fs::write(&input, b"jpeg").unwrap();
+    stub_behaviour(&input, "echo '40 30'\n");
+    
+    let result = call_tool(&client, "transform_image", json!({
+        "input_path": input,
+        "output_path": output,
+        "resize": {"width": 40, "height": 30, "mode": "fill"},
+        "flop": true
+    }))
+    .await;
+    
+    assert_eq!(result["structuredContent"]["width"], 40, "{}", result);
+    assert_eq!(result["structuredContent"]["height"], 30);
+    let argv = recorded_argv(&output);
+    assert_eq!(argv, [
+        input.display().to_string(),
+        "-auto-orient".to_string(),
+        "-resize".to_string(),
+        "40x30^".to_string(),
+        "-gravity".to_string(),
+        "Center".to_string(),
+        "-extent".to_string(),
+        "40x30".to_string(),
+        "+gravity".to_string(),
+        "-flop".to_string(),
+        "-print".to_string(),
+        "%w %h\\n".to_string(),
+        format!("PNG:{}", output.display()),
+    ]);
+}
+
+/// `(op, converter, invocation)` for each step a pipeline reported
+fn step_runs(result: &Value) -> Vec<(String, String, u64)> {
+    result["structuredContent"]["steps"]
+        .as_array()
+        .unwrap_or_else(|| panic!("no steps in {}", result))
+        .iter()
+        .map(|step| {
+            (
+                step["op"].as_str().unwrap().to_string(),
+                step["converter"].as_str().unwrap().to_string(),
+                step["invocation"].as_u64().unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn run(op: &str, converter: &str, invocation: u64) -> (String, String, u64) {
+    (op.to_string(), converter.to_string(), invocation)
+}
+
+#[tokio::test]
+async fn develop_and_encode_run_in_darktable_alone() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.dng");
+    let output = dir.path().join("out.webp");
+    std::fs::write(&input, b"dng").unwrap();
+    
+    let result = call_tool(&client, "process_pipeline", json!({
+        "input_path": input,
+        "output_path": output,
+        "steps": [{"op": "develop"}, {"op": "encode", "webp": {"lossless": false, "quality": 80}}]
+    }))
+    .await;
+    
+    assert_eq!(result["structuredContent"]["invocations"], 1, "{}", result);
+    assert_eq!(step_runs(&result), [run("develop", "darktable", 1), run("encode", "darktable", 1)]);
+    assert_eq!(recorded_argv(&output), [
+        input.display().to_string(),
+        output.display().to_string(),
+        "--core".to_string(),
+        "--conf".to_string(),
+        "plugins/imageio/format/webp/comp_type=0".to_string(),
+        "--conf".to_string(),
+        "plugins/imageio/format/webp/quality=80".to_string(),
+    ]);
+}
+
+#[tokio::test]
+async fn develop_hands_over_to_imagemagick_when_darktable_cannot_finish() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.dng");
+    std::fs::write(&input, b"dng").unwrap();
+    
+    let cases = [
+        // An operation between develop and encode
+        ("resized.webp", json!([{"op": "develop"}, {"op": "resize", "width": 100}, {"op": "encode"}])),
+        // An encoder setting darktable lacks
+        ("alpha.webp", json!([{"op": "develop"}, {"op": "encode", "webp": {"lossless": false, "alpha_quality": 50}}])),
+        // A format the output extension doesn't name
+        ("named.img", json!([{"op": "develop"}, {"op": "encode", "format": "webp"}])),
+    ];
+    for (name, steps) in cases {
+        let output = dir.path().join(name);
+        
+        let result = call_tool(&client, "process_pipeline", json!({
+            "input_path": input,
+            "output_path": output,
+            "steps": steps
+        }))
+        .await;
+        
+        assert_eq!(result["structuredContent"]["invocations"], 2, "{}: {}", name, result);
+        let runs = step_runs(&result);
+        assert_eq!(runs[0], run("develop", "darktable", 1), "{}", name);
+        assert!(runs[1..].iter().all(|(_, converter, invocation)| converter == "imagemagick" && *invocation == 2), "{}: {:?}", name, runs);
+        let argv = recorded_argv(&output);
+        assert!(argv[0].ends_with("developed.tif"), "{}: {:?}", name, argv);
+        assert_eq!(argv.last().unwrap(), &format!("WEBP:{}", output.display()));
+    }
+}
+
+#[tokio::test]
+async fn pipelines_without_develop_run_imagemagick_once() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    let output = dir.path().join("out.webp");
+    std::fs::write(&input, b"png").unwrap();
+    
+    let result = call_tool(&client, "process_pipeline", json!({
+        "input_path": input,
+        "output_path": output,
+        "steps": [{"op": "resize", "width": 100}, {"op": "sharpen"}, {"op": "encode", "webp": {"lossless": false, "quality": 70}}]
+    }))
+    .await;
+    
+    assert_eq!(result["structuredContent"]["invocations"], 1, "{}", result);
+    assert_eq!(step_runs(&result), [
+        run("resize", "imagemagick", 1),
+        run("sharpen", "imagemagick", 1),
+        run("encode", "imagemagick", 1),
+    ]);
+    let argv = recorded_argv(&output);
+    assert_eq!(argv[..6], [
+        input.display().to_string(),
+        "-resize".to_string(),
+        "100".to_string(),
+        "-unsharp".to_string(),
+        "0x1+1+0.05".to_string(),
+        "-quality".to_string(),
+    ]);
+}
+
+#[tokio::test]
+async fn pipeline_fill_resizes_do_not_pad_with_only_shrink() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    let output = dir.path().join("out.webp");
+    std::fs::write(&input, b"png").unwrap();
+    
+    call_tool(&client, "process_pipeline", json!({
+        "input_path": input,
+        "output_path": output,
+        "steps": [{"op": "resize", "width": 400, "height": 300, "mode": "fill", "only_shrink": true}, {"op": "encode"}]
+    }))
+    .await;
+    
+    let argv = recorded_argv(&output);
+    assert_eq!(argv[1..9], [
+        "-resize", "400x300^>", "-gravity", "Center", "-crop", "400x300+0+0", "+repage", "+gravity",
+    ]);
+    assert!(!argv.iter().any(|arg| arg == "-extent"), "{:?}", argv);
+}
+
+#[tokio::test]
+async fn batches_can_write_next_to_their_inputs() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let shoot = dir.path().join("shoot");
+    std::fs::create_dir_all(shoot.join("raw")).unwrap();
+    for name in ["a.jpg", "b.png", "raw/c.png"] {
+        std::fs::write(shoot.join(name), b"image").unwrap();
+    }
+    // Written by an earlier run of the same batch
+    std::fs::write(shoot.join("a.webp"), b"converted earlier").unwrap();
+    
+    let result = call_tool(&client, "batch_convert", json!({"input": shoot, "output_dir": shoot})).await;
+    
+    let files = result["structuredContent"]["files"].as_array().unwrap();
+    let converted: Vec<(&str, &str)> = files
+        .iter()
+        .map(|f| (f["input_path"].as_str().unwrap(), f["status"].as_str().unwrap()))
+        .collect();
+    let a = shoot.join("a.jpg").display().to_string();
+    let b = shoot.join("b.png").display().to_string();
+    assert_eq!(converted, [(a.as_str(), "converted"), (b.as_str(), "converted")], "{}", result);
+    assert_eq!(std::fs::read(shoot.join("a.webp")).unwrap(), b"image\n");
+    
+    // Inputs in a subdirectory of the output directory
+    let result = call_tool(&client, "batch_convert", json!({"input": shoot.join("raw"), "output_dir": shoot})).await;
+    
+    assert_eq!(result["structuredContent"]["converted"], 1, "{}", result);
+    assert!(shoot.join("c.webp").exists());
+}
+
+/// Call `name` with a progress token, returning the `(progress, total)`
+/// pairs notified before the response and the response's result. Fails if
+/// progress is notified after the response.
+async fn call_with_progress(client: &MemoryTransport, name: &str, arguments: Value) -> (Vec<(u64, u64)>, Value) {
+    client.write_message(&json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": {"name": name, "arguments": arguments, "_meta": {"progressToken": "job"}}
+    }))
+    .await
+    .unwrap();
+    
+    let mut progress = Vec::new();
+    let result = loop {
+        let message = recv(client).await;
+        if message["method"] == "notifications/progress" {
+            assert_eq!(message["params"]["progressToken"], "job", "{}", message);
+            let params = &message["params"];
+            progress.push((params["progress"].as_u64().unwrap(), params["total"].as_u64().unwrap()));
+        } else {
+            assert_eq!(message["id"], 2, "{}", message);
+            break message["result"].clone();
+        }
+    };
+    assert_ne!(result["isError"], true, "{}", result);
+    
+    let late = tokio::time::timeout(Duration::from_millis(100), client.read_message()).await;
+    assert!(late.is_err(), "message after the response: {:?}", late);
+    (progress, result)
+}
+
+/// `(progress, total)` for every step from 0 to `total`
+fn every_phase(total: u64) -> Vec<(u64, u64)> {
+    (0..=total).map(|progress| (progress, total)).collect()
+}
+
+#[tokio::test]
+async fn conversions_report_each_phase_before_answering() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    std::fs::write(&input, b"png").unwrap();
+    
+    // Rendering a preview is one more phase
+    for (return_preview, phases) in [(false, 3), (true, 4)] {
+        let output = dir.path().join(format!("out_{}.webp", return_preview));
+        
+        let (progress, _) = call_with_progress(&client, "convert_image", json!({
+            "input_path": input,
+            "output_path": output,
+            "return_preview": return_preview
+        }))
+        .await;
+        
+        assert_eq!(progress, every_phase(phases), "return_preview: {}", return_preview);
+    }
+}
+
+#[tokio::test]
+async fn pipelines_report_each_converter_run() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.dng");
+    std::fs::write(&input, b"dng").unwrap();
+    
+    let (progress, result) = call_with_progress(&client, "process_pipeline", json!({
+        "input_path": input,
+        "output_path": dir.path().join("out.webp"),
+        "steps": [{"op": "develop"}, {"op": "resize", "width": 100}, {"op": "encode"}]
+    }))
+    .await;
+    
+    // Waiting, darktable, ImageMagick, verifying, done
+    assert_eq!(result["structuredContent"]["invocations"], 2, "{}", result);
+    assert_eq!(progress, every_phase(4));
+}
+
+#[tokio::test]
+async fn batches_report_files_done() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a.png", "b.png", "c.png", "d.png", "e.png"] {
+        std::fs::write(dir.path().join(name), b"png").unwrap();
+    }
+    
+    let (progress, result) = call_with_progress(&client, "batch_convert", json!({
+        "input": dir.path(),
+        "output_dir": dir.path().join("out"),
+        "max_parallel": 3
+    }))
+    .await;
+    
+    assert_eq!(result["structuredContent"]["converted"], 5, "{}", result);
+    // Files finishing together may leave gaps, never a step back
+    assert!(progress.iter().all(|&(_, total)| total == 5), "{:?}", progress);
+    assert!(progress.windows(2).all(|pair| pair[0].0 < pair[1].0), "{:?}", progress);
+    assert_eq!(progress.first(), Some(&(0, 5)));
+    assert_eq!(progress.last(), Some(&(5, 5)));
+}
+
+#[tokio::test]
+async fn batch_templates_name_the_resize_box() {
+    let client = start_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.png"), b"png").unwrap();
+    
+    let result = call_tool(&client, "batch_convert", json!({
+        "input": dir.path(),
+        "output_dir": dir.path().join("out"),
+        "filename_template": "{stem}_{box_width}x{box_height}.webp",
+        "resize": {"width": 1600, "height": 1200, "only_shrink": true}
+    }))
+    .await;
+    
+    assert_eq!(result["structuredContent"]["converted"], 1, "{}", result);
+    assert!(dir.path().join("out/a_1600x1200.webp").exists(), "{}", result);
+}
@@ -0,0 +1,64 @@
+//! Error codes and structured error data
+
+use mcp_imagemagick::McpImageError;
+use serde_json::json;
+
+#[test]
+fn protocol_errors_have_stable_codes() {
+    let cases = [
+        (McpImageError::InvalidRequest("x".into()), -32600),
+        (McpImageError::MethodNotFound("x".into()), -32601),
+        (McpImageError::InvalidParams("x".into()), -32602),
+        (McpImageError::NotInitialized("x".into()), -32000),
+        (McpImageError::ResourceNotFound("x".into()), -32002),
+        (McpImageError::Cancelled, -32800),
+        (McpImageError::Mcp("x".into()), -32603),
+    ];
+    for (error, code) in cases {
+        assert_eq!(error.to_jsonrpc_error()["code"], code, "{}", error);
+    }
+}
+
+#[test]
+fn typed_errors_have_stable_payloads() {
+    let cases = [
+        (McpImageError::FileNotFound("/shoot/a.dng".into()), json!({
+            "code": -32005,
+            "message": "File not found",
+            "data": {"kind": "file_not_found", "detail": "File not found: /shoot/a.dng"}
+        })),
+        (McpImageError::InvalidParams("missing tool name".into()), json!({
+            "code": -32602,
+            "message": "Invalid params",
+            "data": {"kind": "invalid_params", "detail": "Invalid params: missing tool name"}
+        })),
+        (McpImageError::Cancelled, json!({
+            "code": -32800,
+            "message": "Request cancelled",
+            "data": {"kind": "cancelled", "detail": "Request cancelled"}
+        })),
+    ];
+    for (error, expected) in cases {
+        assert_eq!(error.to_jsonrpc_error(), expected, "{}", error);
+    }
+}
+
+#[test]
+fn converter_failures_report_process_details() {
+    let error = McpImageError::ConverterFailed {
+        converter: "imagemagick".into(),
+        exit_status: Some(1),
+        stderr: "no decode delegate for this image format".into(),
+    };
+    
+    assert!(error.is_tool_error());
+    assert_eq!(
+        error.to_string(),
+        "imagemagick failed (exit status 1): no decode delegate for this image format"
+    );
+    let data = error.data();
+    assert_eq!(data["kind"], "converter_failed");
+    assert_eq!(data["converter"], "imagemagick");
+    assert_eq!(data["exit_status"], 1);
+    assert_eq!(data["stderr"], "no decode delegate for this image format");
+}
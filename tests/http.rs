@@ -0,0 +1,206 @@
+//! Streamable HTTP transport tests against a server bound to 127.0.0.1
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use mcp_imagemagick::transport::HttpTransport;
+use mcp_imagemagick::McpImageServer;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use common::install_stubs;
+
+async fn start_server() -> SocketAddr {
+    start_server_with_timeout(mcp_imagemagick::transport::DEFAULT_SESSION_TIMEOUT).await
+}
+
+async fn start_server_with_timeout(session_timeout: Duration) -> SocketAddr {
+    start_server_with(session_timeout, None).await
+}
+
+async fn start_server_with(session_timeout: Duration, bearer_token: Option<&str>) -> SocketAddr {
+    // A `magick` that writes the output named by its last argument
+    install_stubs(&[(
+        "magick",
+        "#!/bin/sh\nfor arg; do out=$arg; done\necho image > \"${out#*:}\"\n",
+    )]);
+    let transport = HttpTransport::bind("127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap()
+        .with_session_timeout(session_timeout)
+        .with_bearer_token(bearer_token.map(String::from));
+    let addr = transport.local_addr().unwrap();
+    tokio::spawn(transport.serve_with_shutdown(McpImageServer::new(), std::future::pending()));
+    addr
+}
+
+/// Minimal HTTP/1.1 POST returning the raw response head and body
+async fn post(addr: SocketAddr, session: Option<&str>, body: &Value) -> (String, String) {
+    let headers = session.map(|session| format!("Mcp-Session-Id: {}\r\n", session)).unwrap_or_default();
+    post_with_headers(addr, &headers, body).await
+}
+
+/// POST with `headers`, each line ending in CRLF, added to the request
+async fn post_with_headers(addr: SocketAddr, headers: &str, body: &Value) -> (String, String) {
+    let body = body.to_string();
+    let request = format!(
+        "POST /mcp HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Accept: application/json, text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n{}",
+        addr,
+        body.len(),
+        headers,
+        body
+    );
+    
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    (head.to_string(), body.to_string())
+}
+
+fn session_id(head: &str) -> Option<String> {
+    head.lines()
+        .find_map(|line| line.strip_prefix("mcp-session-id: "))
+        .map(|id| id.trim().to_string())
+}
+
+#[tokio::test]
+async fn initialize_creates_session() {
+    let addr = start_server().await;
+    
+    let (head, body) = post(addr, None, &json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {}
+    }))
+    .await;
+    
+    assert!(head.starts_with("HTTP/1.1 200"));
+    let session = session_id(&head).expect("missing session header");
+    let response: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(response["id"], 1);
+    
+    let (head, _) = post(addr, Some(&session), &json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/initialized"
+    }))
+    .await;
+    assert!(head.starts_with("HTTP/1.1 202"));
+}
+
+#[tokio::test]
+async fn requests_need_a_known_session() {
+    let addr = start_server().await;
+    let list = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+    
+    let (head, _) = post(addr, None, &list).await;
+    assert!(head.starts_with("HTTP/1.1 400"));
+    
+    let (head, body) = post(addr, Some("not-a-session"), &list).await;
+    assert!(head.starts_with("HTTP/1.1 404"));
+    let response: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(response["error"]["code"], -32600);
+    assert_eq!(response["error"]["data"]["kind"], "invalid_request", "{}", response);
+}
+
+#[tokio::test]
+async fn bearer_token_is_required_when_set() {
+    let addr = start_server_with(mcp_imagemagick::transport::DEFAULT_SESSION_TIMEOUT, Some("s3cret")).await;
+    let initialize = json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}});
+    
+    for headers in ["", "Authorization: Bearer wrong\r\n", "Authorization: s3cret\r\n"] {
+        let (head, _) = post_with_headers(addr, headers, &initialize).await;
+        assert!(head.starts_with("HTTP/1.1 401"), "{:?}: {}", headers, head);
+        assert!(head.contains("www-authenticate: Bearer"), "{}", head);
+    }
+    
+    let (head, _) = post_with_headers(addr, "Authorization: Bearer s3cret\r\n", &initialize).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    let session = session_id(&head).expect("missing session header");
+    
+    // The session id alone is not enough
+    let list = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+    let (head, _) = post(addr, Some(&session), &list).await;
+    assert!(head.starts_with("HTTP/1.1 401"), "{}", head);
+    let headers = format!("Authorization: Bearer s3cret\r\nMcp-Session-Id: {}\r\n", session);
+    let (head, _) = post_with_headers(addr, &headers, &list).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
+
+async fn open_session(addr: SocketAddr) -> String {
+    let (head, _) = post(addr, None, &json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}})).await;
+    session_id(&head).expect("missing session header")
+}
+
+#[tokio::test]
+async fn idle_sessions_expire_unless_a_stream_is_open() {
+    let addr = start_server_with_timeout(Duration::from_millis(200)).await;
+    let idle = open_session(addr).await;
+    let streaming = open_session(addr).await;
+    
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let get = format!(
+        "GET /mcp HTTP/1.1\r\nHost: {}\r\nAccept: text/event-stream\r\nMcp-Session-Id: {}\r\n\r\n",
+        addr, streaming
+    );
+    stream.write_all(get.as_bytes()).await.unwrap();
+    let mut head = [0; 12];
+    stream.read_exact(&mut head).await.unwrap();
+    assert_eq!(&head, b"HTTP/1.1 200");
+    
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    
+    let list = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"});
+    let (head, _) = post(addr, Some(&idle), &list).await;
+    assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+    let (head, _) = post(addr, Some(&streaming), &list).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
+
+#[tokio::test]
+async fn progress_is_streamed_before_the_response() {
+    let addr = start_server().await;
+    let session = open_session(addr).await;
+    post(addr, Some(&session), &json!({"jsonrpc": "2.0", "method": "notifications/initialized"})).await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    std::fs::write(&input, b"png").unwrap();
+    
+    let (head, body) = post(addr, Some(&session), &json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": {
+            "name": "convert_image",
+            "arguments": {"input_path": input, "output_path": dir.path().join("out.webp")},
+            "_meta": {"progressToken": "job"}
+        }
+    }))
+    .await;
+    
+    assert!(head.contains("content-type: text/event-stream"), "{}", head);
+    let events: Vec<Value> = body
+        .lines()
+        .filter_map(|line| line.strip_prefix("data: "))
+        .map(|data| serde_json::from_str(data).unwrap())
+        .collect();
+    let (response, progress) = events.split_last().expect("no events");
+    assert_eq!(response["id"], 2, "{}", response);
+    assert_ne!(response["result"]["isError"], true, "{}", response);
+    let progress: Vec<(u64, u64)> = progress
+        .iter()
+        .map(|event| {
+            assert_eq!(event["method"], "notifications/progress", "{}", event);
+            assert_eq!(event["params"]["progressToken"], "job");
+            (event["params"]["progress"].as_u64().unwrap(), event["params"]["total"].as_u64().unwrap())
+        })
+        .collect();
+    assert_eq!(progress, [(0, 3), (1, 3), (2, 3), (3, 3)]);
+}
@@ -0,0 +1,59 @@
+//! Forwarding of log events to clients as `notifications/message`
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use mcp_imagemagick::logging::ClientLogLayer;
+use mcp_imagemagick::transport::{LineTransport, Transport};
+use mcp_imagemagick::{McpImageServer, ServerConfig};
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use common::{initialize, request};
+
+#[tokio::test]
+async fn failed_writes_do_not_flood_other_sessions() {
+    tracing_subscriber::registry().with(ClientLogLayer::layer()).init();
+    
+    let (client, _server) = common::start_server(ServerConfig::default());
+    initialize(&client).await;
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "logging/setLevel",
+        "params": {"level": "error"}
+    }))
+    .await;
+    assert_eq!(response["result"], json!({}));
+    
+    // A connection that can still be read from but whose peer stopped reading
+    let (mut input, server_input) = tokio::io::duplex(4096);
+    let (server_output, closed) = tokio::io::duplex(4096);
+    drop(closed);
+    let broken = LineTransport::from_io(server_input, server_output);
+    tokio::spawn(Arc::new(McpImageServer::new()).serve(broken));
+    for message in [
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+        json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+        json!({"jsonrpc": "2.0", "id": 2, "method": "ping"}),
+    ] {
+        input.write_all(format!("{}\n", message).as_bytes()).await.unwrap();
+    }
+    
+    let mut forwarded = Vec::new();
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(300);
+    while let Ok(message) = tokio::time::timeout_at(deadline, client.read_message()).await {
+        forwarded.push(message.expect("server closed the connection"));
+    }
+    let failures = forwarded
+        .iter()
+        .filter(|m| m["method"] == "notifications/message")
+        .filter(|m| m["params"]["data"].as_str().is_some_and(|d| d.contains("Failed to write")))
+        .count();
+    assert_eq!(failures, 0, "write failures were forwarded");
+    assert!(forwarded.len() < 50, "{} messages forwarded", forwarded.len());
+}
@@ -0,0 +1,48 @@
+//! Reading image dimensions from file headers
+
+use mcp_imagemagick::handlers::probe::image_dimensions;
+
+async fn dimensions_of(bytes: &[u8]) -> Option<(u32, u32)> {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), bytes).unwrap();
+    image_dimensions(file.path()).await
+}
+
+#[tokio::test]
+async fn reads_png_and_gif_headers() {
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+    png.extend_from_slice(&640u32.to_be_bytes());
+    png.extend_from_slice(&480u32.to_be_bytes());
+    assert_eq!(dimensions_of(&png).await, Some((640, 480)));
+    
+    let gif = b"GIF89a\x20\x03\x58\x02";
+    assert_eq!(dimensions_of(gif).await, Some((800, 600)));
+}
+
+#[tokio::test]
+async fn reads_webp_headers() {
+    // Lossless: 14-bit width-1 and height-1 after the 0x2f signature
+    let bits: u32 = (4000 - 1) | ((3000 - 1) << 14);
+    let mut lossless = b"RIFF\0\0\0\0WEBPVP8L\0\0\0\0\x2f".to_vec();
+    lossless.extend_from_slice(&bits.to_le_bytes());
+    assert_eq!(dimensions_of(&lossless).await, Some((4000, 3000)));
+    
+    // Extended: 24-bit canvas width-1 and height-1
+    let mut extended = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0\0\0\0\0".to_vec();
+    extended.extend_from_slice(&[0x3f, 0x1f, 0x00, 0xff, 0x0f, 0x00]);
+    assert_eq!(dimensions_of(&extended).await, Some((8000, 4096)));
+}
+
+#[tokio::test]
+async fn reads_jpeg_frame_after_other_segments() {
+    let mut jpeg = b"\xff\xd8".to_vec();
+    jpeg.extend_from_slice(b"\xff\xe1\x00\x06Exif");
+    jpeg.extend_from_slice(b"\xff\xc0\x00\x11\x08\x04\x38\x07\x80");
+    assert_eq!(dimensions_of(&jpeg).await, Some((1920, 1080)));
+}
+
+#[tokio::test]
+async fn unknown_formats_have_no_dimensions() {
+    assert_eq!(dimensions_of(b"II*\0 not parsed").await, None);
+    assert_eq!(dimensions_of(b"").await, None);
+}
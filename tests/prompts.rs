@@ -0,0 +1,143 @@
+//! Prompt templates over an in-memory transport
+
+mod common;
+
+use mcp_imagemagick::transport::MemoryTransport;
+use mcp_imagemagick::ServerConfig;
+use serde_json::json;
+
+use common::{initialize, request};
+
+async fn start_server(config: ServerConfig) -> MemoryTransport {
+    let (client, _server) = common::start_server(config);
+    initialize(&client).await;
+    client
+}
+
+#[tokio::test]
+async fn builtin_prompts_are_listed() {
+    let client = start_server(ServerConfig::default()).await;
+    
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 1, "method": "prompts/list"})).await;
+    
+    let names: Vec<_> = response["result"]["prompts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["archive_raw", "contact_sheet", "publish_web"]);
+}
+
+#[tokio::test]
+async fn prompts_are_rendered_with_arguments_and_defaults() {
+    let client = start_server(ServerConfig::default()).await;
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "prompts/get",
+        "params": {
+            "name": "publish_web",
+            "arguments": {"input_dir": "/shoot", "output_dir": "/web"}
+        }
+    }))
+    .await;
+    
+    let message = &response["result"]["messages"][0];
+    assert_eq!(message["role"], "user");
+    let text = message["content"]["text"].as_str().unwrap();
+    assert!(text.contains("/shoot") && text.contains("/web") && text.contains("2048"));
+    assert!(!text.contains("{{"));
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "prompts/get",
+        "params": {"name": "publish_web", "arguments": {"input_dir": "/shoot"}}
+    }))
+    .await;
+    assert_eq!(response["error"]["code"], -32602);
+}
+
+#[tokio::test]
+async fn builtin_workflows_use_batch_convert() {
+    let client = start_server(ServerConfig::default()).await;
+    
+    for (id, name) in [(1, "archive_raw"), (2, "publish_web")] {
+        let response = request(&client, json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "prompts/get",
+            "params": {"name": name, "arguments": {"input_dir": "/shoot", "output_dir": "/web", "archive_dir": "/archive"}}
+        }))
+        .await;
+        
+        let text = response["result"]["messages"][0]["content"]["text"].as_str().unwrap();
+        assert!(text.contains("batch_convert"), "{}: {}", name, text);
+        assert!(!text.contains("convert_dng_to_webp"), "{}: {}", name, text);
+    }
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 3,
+        "method": "prompts/get",
+        "params": {"name": "publish_web", "arguments": {"input_dir": "/shoot", "output_dir": "/web"}}
+    }))
+    .await;
+    let text = response["result"]["messages"][0]["content"]["text"].as_str().unwrap();
+    assert!(text.contains("\"only_shrink\": true"), "{}", text);
+    assert!(text.contains("No tool on this server removes metadata"), "{}", text);
+}
+
+#[tokio::test]
+async fn placeholders_in_argument_values_are_not_expanded() {
+    let client = start_server(ServerConfig::default()).await;
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "prompts/get",
+        "params": {
+            "name": "publish_web",
+            "arguments": {"input_dir": "/shoot/{{output_dir}}", "output_dir": "/web/{{max_dimension}}"}
+        }
+    }))
+    .await;
+    
+    let text = response["result"]["messages"][0]["content"]["text"].as_str().unwrap();
+    assert!(text.starts_with("Publish the photos in /shoot/{{output_dir}} to /web/{{max_dimension}} for the web."), "{}", text);
+}
+
+#[tokio::test]
+async fn prompts_are_loaded_from_a_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("greyscale.json"),
+        r#"{
+            "name": "greyscale",
+            "arguments": [{"name": "path", "required": true}],
+            "messages": [{"text": "Make {{path}} black and white"}]
+        }"#,
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("broken.json"), "{").unwrap();
+    let config = ServerConfig {
+        prompts_dir: Some(dir.path().to_path_buf()),
+        ..ServerConfig::default()
+    };
+    let client = start_server(config).await;
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "prompts/get",
+        "params": {"name": "greyscale", "arguments": {"path": "/a.png"}}
+    }))
+    .await;
+    
+    assert_eq!(
+        response["result"]["messages"][0]["content"]["text"],
+        "Make /a.png black and white"
+    );
+}
@@ -0,0 +1,869 @@
+//! End-to-end protocol tests driving `McpImageServer` over an in-memory transport
+
+mod common;
+
+use std::path::Path;
+use std::time::Duration;
+
+use mcp_imagemagick::transport::{MemoryTransport, Transport};
+use mcp_imagemagick::ServerConfig;
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
+
+use common::{initialize, initialize_version, install_stubs, recv, request};
+
+/// Make a stub `darktable-cli` the only converter, so the conversion tools
+/// are listed whatever is installed on the host. It fails every conversion,
+/// after sourcing `<input>.stub` when a test leaves one next to the input.
+fn install_stub_converter() {
+    install_stubs(&[(
+        "darktable-cli",
+        "#!/bin/sh\n[ -f \"$1.stub\" ] && . \"$1.stub\"\necho 'stub converter' >&2\nexit 1\n",
+    )]);
+}
+
+fn start_server() -> (MemoryTransport, JoinHandle<mcp_imagemagick::Result<()>>) {
+    start_server_with(ServerConfig::default())
+}
+
+fn start_server_with(config: ServerConfig) -> (MemoryTransport, JoinHandle<mcp_imagemagick::Result<()>>) {
+    install_stub_converter();
+    common::start_server(config)
+}
+
+#[tokio::test]
+async fn initialize_reports_server_info() {
+    let (client, _server) = start_server();
+    
+    let response = initialize(&client).await;
+    
+    assert_eq!(response["id"], 1);
+    assert_eq!(response["result"]["serverInfo"]["name"], "mcp-imagemagick");
+    assert!(response["result"]["capabilities"]["tools"].is_object());
+}
+
+#[tokio::test]
+async fn notifications_are_not_answered() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    
+    client
+        .write_message(&json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/cancelled",
+            "params": {"requestId": 99}
+        }))
+        .await
+        .unwrap();
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"})).await;
+    
+    // The first thing written back is the tools/list response
+    assert_eq!(response["id"], 2);
+    assert!(response["result"]["tools"].is_array());
+}
+
+#[tokio::test]
+async fn unknown_method_is_rejected() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 3, "method": "no/such"})).await;
+    
+    assert_eq!(response["id"], 3);
+    assert_eq!(response["error"]["code"], -32601);
+}
+
+#[tokio::test]
+async fn session_ends_when_client_disconnects() {
+    let (client, server) = start_server();
+    initialize(&client).await;
+    
+    drop(client);
+    
+    let result = tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server did not stop")
+        .unwrap();
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn batch_returns_one_response_per_request() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    
+    let response = request(&client, json!([
+        {"jsonrpc": "2.0", "id": 10, "method": "tools/list"},
+        {"jsonrpc": "2.0", "method": "notifications/cancelled", "params": {"requestId": 1}},
+        {"jsonrpc": "2.0", "id": 11, "method": "no/such"}
+    ]))
+    .await;
+    
+    let responses = response.as_array().expect("batch reply must be an array");
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["id"], 10);
+    assert!(responses[0]["result"]["tools"].is_array());
+    assert_eq!(responses[1]["id"], 11);
+    assert_eq!(responses[1]["error"]["code"], -32601);
+}
+
+#[tokio::test]
+async fn empty_batch_is_invalid() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    
+    let response = request(&client, json!([])).await;
+    
+    assert_eq!(response["id"], Value::Null);
+    assert_eq!(response["error"], json!({
+        "code": -32600,
+        "message": "Invalid Request",
+        "data": {"kind": "invalid_request", "detail": "Invalid request: empty batch"}
+    }));
+    
+    let response = request(&client, json!({"jsonrpc": "1.0", "id": 2, "method": "ping"})).await;
+    assert_eq!(response["id"], 2);
+    assert_eq!(response["error"]["data"]["kind"], "invalid_request", "{}", response);
+    assert_eq!(response["error"]["data"]["detail"], "Invalid request: missing or invalid jsonrpc version");
+}
+
+#[tokio::test]
+async fn initialize_negotiates_protocol_version() {
+    for (requested, expected) in [
+        ("2025-03-26", "2025-03-26"),
+        ("2025-06-18", "2025-06-18"),
+        ("1999-01-01", "2025-06-18"),
+    ] {
+        let (client, _server) = start_server();
+        
+        let response = request(&client, json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {"protocolVersion": requested, "capabilities": {}}
+        }))
+        .await;
+        
+        assert_eq!(response["result"]["protocolVersion"], expected, "requested {}", requested);
+    }
+}
+
+#[tokio::test]
+async fn requests_before_initialize_are_rejected() {
+    let (client, _server) = start_server();
+    
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"})).await;
+    
+    assert_eq!(response["error"]["code"], -32000);
+}
+
+#[tokio::test]
+async fn early_initialized_notification_is_ignored() {
+    let (client, _server) = start_server();
+    client
+        .write_message(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+        .await
+        .unwrap();
+    
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"})).await;
+    assert_eq!(response["error"]["code"], -32000, "{}", response);
+    
+    let response = initialize(&client).await;
+    assert!(response["result"]["protocolVersion"].is_string(), "{}", response);
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"})).await;
+    assert!(response["result"]["tools"].is_array(), "{}", response);
+}
+
+#[tokio::test]
+async fn ping_and_set_level_are_answered() {
+    let (client, _server) = start_server();
+    
+    // ping is allowed even before initialize
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 1, "method": "ping"})).await;
+    assert_eq!(response["result"], json!({}));
+    
+    let init = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "initialize",
+        "params": {"protocolVersion": "2025-06-18", "capabilities": {}}
+    }))
+    .await;
+    assert!(init["result"]["capabilities"]["logging"].is_object());
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 3,
+        "method": "logging/setLevel",
+        "params": {"level": "debug"}
+    }))
+    .await;
+    assert_eq!(response["result"], json!({}));
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 4,
+        "method": "logging/setLevel",
+        "params": {"level": "chatty"}
+    }))
+    .await;
+    assert_eq!(response["error"]["code"], -32602);
+}
+
+#[tokio::test]
+async fn structured_output_follows_protocol_version() {
+    for (version, structured) in [("2025-06-18", true), ("2025-03-26", false)] {
+        let (client, _server) = start_server();
+        initialize_version(&client, version).await;
+        
+        let tools = request(&client, json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"})).await;
+        for tool in tools["result"]["tools"].as_array().unwrap() {
+            assert_eq!(tool["outputSchema"].is_object(), structured, "{} on {}", tool["name"], version);
+        }
+        
+        let response = request(&client, json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "tools/call",
+            "params": {"name": "check_converters", "arguments": {}}
+        }))
+        .await;
+        let result = &response["result"];
+        assert_eq!(result["content"][0]["type"], "text");
+        assert_eq!(result["structuredContent"]["available_count"].is_u64(), structured, "{}", version);
+    }
+}
+
+#[tokio::test]
+async fn tool_failures_are_tool_results() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": {
+            "name": "convert_dng_to_webp",
+            "arguments": {"input_path": "/no/such/file.dng", "output_path": "/tmp/out.webp"}
+        }
+    }))
+    .await;
+    
+    assert!(response.get("error").is_none(), "{}", response);
+    assert_eq!(response["result"]["isError"], true);
+    assert_eq!(response["result"]["content"], json!([{"type": "text", "text": "File not found: /no/such/file.dng"}]));
+    assert_eq!(response["result"]["_meta"]["error"], json!({
+        "kind": "file_not_found",
+        "detail": "File not found: /no/such/file.dng"
+    }));
+}
+
+/// Whether `pid` is still running; zombies count as gone
+#[cfg(target_os = "linux")]
+fn process_running(pid: &str) -> bool {
+    match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        // The state follows the parenthesised command name
+        Ok(stat) => !stat.rsplit_once(") ").is_some_and(|(_, rest)| rest.starts_with('Z')),
+        Err(_) => false,
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn cancelled_conversion_is_not_answered_and_cleans_up() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("slow.dng");
+    let output = dir.path().join("slow.webp");
+    let pid_file = dir.path().join("slow.pid");
+    std::fs::write(&input, b"dng").unwrap();
+    // Write part of the output, then hang until killed
+    std::fs::write(
+        dir.path().join("slow.dng.stub"),
+        format!("echo partial > \"$2\"\necho $$ > {}\nexec sleep 30\n", pid_file.display()),
+    )
+    .unwrap();
+    
+    client.write_message(&json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": {"name": "convert_dng_to_webp", "arguments": {"input_path": input, "output_path": output}}
+    }))
+    .await
+    .unwrap();
+    
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    let pid = loop {
+        match std::fs::read_to_string(&pid_file) {
+            Ok(pid) if !pid.trim().is_empty() && output.exists() => break pid.trim().to_string(),
+            _ if tokio::time::Instant::now() > deadline => panic!("stub converter never started"),
+            _ => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    };
+    assert!(process_running(&pid));
+    
+    client.write_message(&json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/cancelled",
+        "params": {"requestId": 2, "reason": "user aborted"}
+    }))
+    .await
+    .unwrap();
+    client.write_message(&json!({"jsonrpc": "2.0", "id": 3, "method": "ping"})).await.unwrap();
+    
+    // Everything up to the ping response must leave request 2 unanswered
+    loop {
+        let message = recv(&client).await;
+        assert_ne!(message["id"], 2, "cancelled request was answered: {}", message);
+        if message["id"] == 3 {
+            break;
+        }
+    }
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while output.exists() || process_running(&pid) {
+        assert!(tokio::time::Instant::now() < deadline, "partial output or converter outlived the cancel");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+fn convert_dng(id: u64, input: &Path) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": {
+            "name": "convert_dng_to_webp",
+            "arguments": {"input_path": input, "output_path": input.with_extension("webp")}
+        }
+    })
+}
+
+#[tokio::test]
+async fn slow_conversions_do_not_hold_up_other_requests() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("slow.dng");
+    let started = dir.path().join("started");
+    std::fs::write(&input, b"dng").unwrap();
+    std::fs::write(
+        dir.path().join("slow.dng.stub"),
+        format!("echo > \"{}\"\nsleep 1\n", started.display()),
+    )
+    .unwrap();
+    
+    client.write_message(&convert_dng(2, &input)).await.unwrap();
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while !started.exists() {
+        assert!(tokio::time::Instant::now() < deadline, "stub converter never started");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    client.write_message(&json!({"jsonrpc": "2.0", "id": 3, "method": "ping"})).await.unwrap();
+    client.write_message(&json!({"jsonrpc": "2.0", "id": 4, "method": "tools/list"})).await.unwrap();
+    
+    let mut answered = Vec::new();
+    for _ in 0..3 {
+        answered.push(recv(&client).await["id"].clone());
+    }
+    assert_eq!(answered, [json!(3), json!(4), json!(2)]);
+}
+
+#[tokio::test]
+async fn conversions_beyond_the_cap_wait_for_a_slot() {
+    let (client, _server) = start_server_with(ServerConfig {
+        max_concurrent_conversions: 2,
+        ..ServerConfig::default()
+    });
+    initialize(&client).await;
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("runs.log");
+    let inputs: Vec<_> = ["a", "b", "c"].iter().map(|name| dir.path().join(format!("{}.dng", name))).collect();
+    for input in &inputs {
+        std::fs::write(input, b"dng").unwrap();
+        let mut stub = input.as_os_str().to_owned();
+        stub.push(".stub");
+        std::fs::write(
+            stub,
+            format!("echo start >> \"{0}\"\nsleep 0.3\necho end >> \"{0}\"\n", log.display()),
+        )
+        .unwrap();
+    }
+    
+    for (id, input) in (2..).zip(&inputs) {
+        client.write_message(&convert_dng(id, input)).await.unwrap();
+    }
+    for _ in &inputs {
+        recv(&client).await;
+    }
+    
+    let mut running = 0;
+    let mut most = 0;
+    let runs = std::fs::read_to_string(&log).unwrap();
+    for line in runs.lines() {
+        running += if line == "start" { 1 } else { -1 };
+        most = most.max(running);
+    }
+    assert_eq!(runs.lines().filter(|line| *line == "start").count(), 3, "{}", runs);
+    assert_eq!(most, 2, "{}", runs);
+}
+
+#[tokio::test]
+async fn unknown_tools_and_bad_arguments_are_protocol_errors() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    
+    for (id, params) in [
+        (2, json!({"name": "no_such_tool", "arguments": {}})),
+        (3, json!({"name": "convert_dng_to_webp", "arguments": {"input_path": 1}})),
+        (4, json!({"name": "convert_dng_to_webp", "arguments": {
+            "input_path": "/a.dng", "output_path": "/a.webp", "converter": "gimp"
+        }})),
+    ] {
+        let response = request(&client, json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": params
+        }))
+        .await;
+        
+        assert_eq!(response["error"]["code"], -32602, "{}", response);
+    }
+}
+
+#[tokio::test]
+async fn convert_image_checks_formats_before_converting() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    std::fs::write(&input, b"png").unwrap();
+    let input = input.to_str().unwrap();
+    
+    for (id, arguments, expected) in [
+        (2, json!({"input_path": input, "output_path": "/tmp/out.png", "format": "jpeg"}), "does not match"),
+        (3, json!({"input_path": input, "output_path": "/tmp/out"}), "Cannot tell the output format"),
+        (4, json!({"input_path": input, "output_path": "/tmp/out.dng"}), "not an output format"),
+        (5, json!({"input_path": "/no/such.png", "output_path": "/tmp/out.gif"}), "File not found"),
+    ] {
+        let response = request(&client, json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": {"name": "convert_image", "arguments": arguments}
+        }))
+        .await;
+        
+        assert_eq!(response["result"]["isError"], true, "{}", response);
+        let text = response["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.contains(expected), "{}", text);
+    }
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 6,
+        "method": "tools/call",
+        "params": {"name": "convert_image", "arguments": {"input_path": input, "output_path": "/tmp/out", "format": "pcx"}}
+    }))
+    .await;
+    assert_eq!(response["error"]["code"], -32602, "{}", response);
+}
+
+#[tokio::test]
+async fn webp_options_are_validated() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    
+    for (id, webp) in [
+        (2, json!({"quality": 101})),
+        (3, json!({"method": 7})),
+        (4, json!({"lossless": false, "near_lossless": 60})),
+        (5, json!({"target_size": 50000})),
+        (6, json!({"lossless": false, "target_size": 0})),
+        (7, json!({"effort": 3})),
+    ] {
+        let response = request(&client, json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": {"name": "convert_dng_to_webp", "arguments": {
+                "input_path": "/a.dng", "output_path": "/a.webp", "webp": webp
+            }}
+        }))
+        .await;
+        
+        assert_eq!(response["error"]["code"], -32602, "{}", response);
+    }
+    
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    std::fs::write(&input, b"png").unwrap();
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 8,
+        "method": "tools/call",
+        "params": {"name": "convert_image", "arguments": {
+            "input_path": input, "output_path": "/tmp/out.jpg", "webp": {"lossless": false}
+        }}
+    }))
+    .await;
+    assert_eq!(response["result"]["isError"], true, "{}", response);
+}
+
+#[tokio::test]
+async fn avif_and_jxl_options_are_validated() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    std::fs::write(&input, b"png").unwrap();
+    
+    for (id, output, options) in [
+        (2, "out.avif", json!({"avif": {"speed": 11}})),
+        (3, "out.avif", json!({"avif": {"bit_depth": 16}})),
+        (4, "out.avif", json!({"avif": {"chroma": "411"}})),
+        (5, "out.avif", json!({"avif": {"lossless": true, "chroma": "420"}})),
+        (6, "out.jxl", json!({"jxl": {"distance": 30.0}})),
+        (7, "out.jxl", json!({"jxl": {"effort": 0}})),
+        (8, "out.jxl", json!({"jxl": {"distance": 1.0, "quality": 90}})),
+        (9, "out.jxl", json!({"jxl": {"lossless": true, "quality": 90}})),
+    ] {
+        let mut arguments = json!({"input_path": input, "output_path": dir.path().join(output)});
+        arguments.as_object_mut().unwrap().extend(options.as_object().unwrap().clone());
+        let response = request(&client, json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": {"name": "convert_image", "arguments": arguments}
+        }))
+        .await;
+        
+        assert_eq!(response["error"]["code"], -32602, "{}", response);
+    }
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 10,
+        "method": "tools/call",
+        "params": {"name": "convert_image", "arguments": {
+            "input_path": input, "output_path": dir.path().join("out.avif"), "jxl": {"effort": 7}
+        }}
+    }))
+    .await;
+    assert_eq!(response["result"]["isError"], true, "{}", response);
+    let text = response["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("jxl options do not apply to AVIF output"), "{}", text);
+}
+
+#[tokio::test]
+async fn transform_arguments_are_validated() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    std::fs::write(&input, b"png").unwrap();
+    
+    for (id, transform) in [
+        (2, json!({"resize": {}})),
+        (3, json!({"resize": {"width": 0}})),
+        (4, json!({"resize": {"width": 100, "mode": "fill"}})),
+        (5, json!({"resize": {"height": 100, "mode": "exact"}})),
+        (6, json!({"resize": {"width": 100, "filter": "bicubic"}})),
+        (7, json!({"crop": {"width": 0, "height": 10}})),
+        (8, json!({"crop": {"width": 10, "height": 10, "gravity": "middle"}})),
+        (9, json!({"rotate": "left"})),
+    ] {
+        let mut arguments = json!({"input_path": input, "output_path": dir.path().join("out.png")});
+        arguments.as_object_mut().unwrap().extend(transform.as_object().unwrap().clone());
+        let response = request(&client, json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": {"name": "transform_image", "arguments": arguments}
+        }))
+        .await;
+        
+        assert_eq!(response["error"]["code"], -32602, "{}", response);
+    }
+}
+
+#[tokio::test]
+async fn pipeline_steps_are_validated() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("in.png");
+    std::fs::write(&input, b"png").unwrap();
+    
+    for (id, steps) in [
+        (2, json!([])),
+        (3, json!([{"op": "blur"}])),
+        (4, json!([{"op": "resize", "width": 100}, {"op": "develop"}])),
+        (5, json!([{"op": "encode"}, {"op": "resize", "width": 100}])),
+        (6, json!([{"op": "resize", "width": 100, "mode": "fill"}])),
+        (7, json!([{"op": "sharpen", "sigma": 0}])),
+        (8, json!([{"op": "color_convert"}])),
+        (9, json!([{"op": "watermark", "image": input, "opacity": 150}])),
+        (10, json!([{"op": "encode", "webp": {"method": 9}}])),
+    ] {
+        let response = request(&client, json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": {"name": "process_pipeline", "arguments": {
+                "input_path": input, "output_path": dir.path().join("out.webp"), "steps": steps
+            }}
+        }))
+        .await;
+        
+        assert_eq!(response["error"]["code"], -32602, "{}", response);
+    }
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 11,
+        "method": "tools/call",
+        "params": {"name": "process_pipeline", "arguments": {
+            "input_path": input,
+            "output_path": dir.path().join("out.webp"),
+            "steps": [{"op": "watermark", "image": dir.path().join("missing.png")}]
+        }}
+    }))
+    .await;
+    assert_eq!(response["result"]["isError"], true, "{}", response);
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 12,
+        "method": "tools/call",
+        "params": {"name": "process_pipeline", "arguments": {
+            "input_path": input,
+            "output_path": dir.path().join("out.avif"),
+            "steps": [{"op": "encode", "format": "avif", "jxl": {"effort": 7}}]
+        }}
+    }))
+    .await;
+    assert_eq!(response["result"]["isError"], true, "{}", response);
+    let text = response["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("jxl options do not apply to AVIF output"), "{}", text);
+}
+
+#[tokio::test]
+async fn batch_arguments_are_validated() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("a.png"), b"png").unwrap();
+    let input = dir.path().to_str().unwrap();
+    
+    // Argument mistakes are protocol errors; only the unwritable format is
+    // a tool error, like in convert_image
+    for (id, arguments, tool_error, detail) in [
+        (2, json!({"filename_template": "{name}.webp"}), false, "unknown placeholder {name} in filename_template"),
+        (3, json!({"filename_template": "{stem}_{box_width}.webp"}), false, "{box_width} in filename_template needs resize.width"),
+        (4, json!({"filename_template": "{stem.webp"}), false, "unclosed { in filename_template \"{stem.webp\""),
+        (5, json!({"filename_template": "../{stem}.webp"}), false, "filename_template must be a file name, not a path"),
+        (6, json!({"filename_template": "{stem}.dng"}), true, "DNG is not an output format"),
+        (7, json!({"max_parallel": 0}), false, "max_parallel must be 1-64"),
+        (
+            8,
+            json!({"input": format!("{}/*.png", input), "recursive": true}),
+            false,
+            "recursive applies to a directory input; use ** in a glob",
+        ),
+        (
+            9,
+            json!({"filename_template": "{stem}_{width}.webp", "resize": {"width": 100}}),
+            false,
+            "unknown placeholder {width} in filename_template; {box_width} is the resize box",
+        ),
+    ] {
+        let mut full = json!({"input": input, "output_dir": dir.path().join("out")});
+        full.as_object_mut().unwrap().extend(arguments.as_object().unwrap().clone());
+        let response = request(&client, json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "tools/call",
+            "params": {"name": "batch_convert", "arguments": full}
+        }))
+        .await;
+        
+        if tool_error {
+            assert_eq!(response["result"]["isError"], true, "{}", response);
+            assert_eq!(response["result"]["content"][0]["text"], format!("Invalid input: {}", detail));
+        } else {
+            assert_eq!(response["error"]["code"], -32602, "{}", response);
+            assert_eq!(response["error"]["data"]["detail"], format!("Invalid params: {}", detail));
+        }
+    }
+    assert!(!dir.path().join("out").exists());
+}
+
+#[tokio::test]
+async fn batch_reports_each_file() {
+    let (client, _server) = start_server();
+    initialize_version(&client, "2025-06-18").await;
+    let dir = tempfile::tempdir().unwrap();
+    let shoot = dir.path().join("shoot");
+    std::fs::create_dir(&shoot).unwrap();
+    for name in ["a.jpg", "a.png", "b.png", "notes.txt"] {
+        std::fs::write(shoot.join(name), b"image").unwrap();
+    }
+    let out = dir.path().join("out");
+    std::fs::create_dir(&out).unwrap();
+    std::fs::write(out.join("b.jpg"), b"converted earlier").unwrap();
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "tools/call",
+        "params": {"name": "batch_convert", "arguments": {
+            "input": shoot,
+            "output_dir": out,
+            "filename_template": "{stem}.jpg",
+            "converter": "darktable",
+            "skip_up_to_date": true
+        }}
+    }))
+    .await;
+    
+    let result = &response["result"]["structuredContent"];
+    assert_eq!(result["total"], 3, "{}", response);
+    assert_eq!(result["skipped"], 1);
+    assert_eq!(result["failed"], 2);
+    let files = result["files"].as_array().unwrap();
+    let status: Vec<&str> = files.iter().map(|f| f["status"].as_str().unwrap()).collect();
+    assert_eq!(status, ["failed", "failed", "skipped"]);
+    // The stub converter fails a.jpg; a.png would overwrite its output
+    assert!(files[0]["error"].as_str().unwrap().contains("darktable"), "{}", files[0]);
+    assert!(files[1]["error"].as_str().unwrap().contains("also the output of"), "{}", files[1]);
+    assert_eq!(std::fs::read(out.join("b.jpg")).unwrap(), b"converted earlier");
+}
+
+#[tokio::test]
+async fn input_schemas_match_argument_types() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"})).await;
+    let tools = response["result"]["tools"].as_array().unwrap();
+    let convert = tools.iter().find(|t| t["name"] == "convert_dng_to_webp").unwrap();
+    let schema = &convert["inputSchema"];
+    
+    assert_eq!(schema["type"], "object");
+    assert_eq!(schema["required"], json!(["input_path", "output_path"]));
+    assert_eq!(schema["properties"]["converter"]["enum"], json!(["auto", "imagemagick", "darktable"]));
+    assert_eq!(schema["properties"]["converter"]["default"], "auto");
+    assert_eq!(schema["properties"]["return_preview"]["type"], "boolean");
+}
+
+#[tokio::test]
+async fn errors_carry_structured_data() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 2, "method": "no/such"})).await;
+    assert_eq!(response["error"], json!({
+        "code": -32601,
+        "message": "Method not found",
+        "data": {"kind": "method_not_found", "detail": "Method not found: no/such"}
+    }));
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 3,
+        "method": "tools/call",
+        "params": {"arguments": {}}
+    }))
+    .await;
+    assert_eq!(response["error"], json!({
+        "code": -32602,
+        "message": "Invalid params",
+        "data": {"kind": "invalid_params", "detail": "Invalid params: missing tool name"}
+    }));
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 4,
+        "method": "tools/call",
+        "params": {
+            "name": "convert_dng_to_webp",
+            "arguments": {"input_path": "/no/such/file.dng", "output_path": "/tmp/out.webp"}
+        }
+    }))
+    .await;
+    assert_eq!(response["result"]["_meta"]["error"], json!({
+        "kind": "file_not_found",
+        "detail": "File not found: /no/such/file.dng"
+    }));
+}
+
+/// Names of the tools in a `tools/list` response
+fn listed_names(response: &Value) -> Vec<String> {
+    response["result"]["tools"]
+        .as_array()
+        .unwrap_or_else(|| panic!("no tools in {}", response))
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn tools_list_pages_follow_the_cursor() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    let all = listed_names(&request(&client, json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"})).await);
+    let (client, _server) = start_server_with(ServerConfig {
+        tools_page_size: 2,
+        ..ServerConfig::default()
+    });
+    initialize(&client).await;
+    
+    let mut listed = Vec::new();
+    let mut params = json!({});
+    for id in 2.. {
+        let response = request(&client, json!({"jsonrpc": "2.0", "id": id, "method": "tools/list", "params": params})).await;
+        let page = listed_names(&response);
+        assert!(!page.is_empty() && page.len() <= 2, "{}", response);
+        listed.extend(page);
+        match response["result"]["nextCursor"].as_str() {
+            Some(cursor) => params = json!({"cursor": cursor}),
+            None => break,
+        }
+    }
+    assert!(all.len() > 2, "{:?}", all);
+    assert_eq!(listed, all);
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 20,
+        "method": "tools/list",
+        "params": {"cursor": "nope"}
+    }))
+    .await;
+    assert_eq!(response["error"]["code"], -32602, "{}", response);
+    assert_eq!(response["error"]["data"]["detail"], "Invalid params: bad cursor \"nope\"");
+}
+
+#[tokio::test]
+async fn tools_are_listed_with_titles_and_annotations() {
+    let (client, _server) = start_server();
+    initialize(&client).await;
+    
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"})).await;
+    let tools = response["result"]["tools"].as_array().unwrap();
+    let tool = |name: &str| tools.iter().find(|t| t["name"] == name).unwrap().clone();
+    
+    let check = tool("check_converters");
+    assert!(check["title"].is_string());
+    assert_eq!(check["annotations"]["readOnlyHint"], true);
+    assert_eq!(check["annotations"]["openWorldHint"], false);
+    
+    let convert = tool("convert_dng_to_webp");
+    assert_eq!(convert["annotations"]["readOnlyHint"], false);
+    assert_eq!(convert["annotations"]["destructiveHint"], true);
+    assert_eq!(convert["annotations"]["idempotentHint"], true);
+}
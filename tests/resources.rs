@@ -0,0 +1,144 @@
+//! Resource listing and reading over an in-memory transport
+
+mod common;
+
+use std::sync::Arc;
+
+use base64::Engine;
+use mcp_imagemagick::handlers::{OutputLog, ResourceHandler};
+use mcp_imagemagick::transport::MemoryTransport;
+use mcp_imagemagick::ServerConfig;
+use serde_json::json;
+
+use common::{initialize, recv, request};
+
+async fn start_server(root: &std::path::Path) -> MemoryTransport {
+    let config = ServerConfig {
+        resource_roots: vec![root.to_path_buf()],
+        ..ServerConfig::default()
+    };
+    let (client, _server) = common::start_server(config);
+    initialize(&client).await;
+    client
+}
+
+#[tokio::test]
+async fn lists_images_under_roots() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::create_dir(root.path().join("raw")).unwrap();
+    std::fs::write(root.path().join("raw/a b.dng"), b"dng").unwrap();
+    std::fs::write(root.path().join("out.webp"), b"webp").unwrap();
+    std::fs::write(root.path().join("notes.txt"), b"skip me").unwrap();
+    let client = start_server(root.path()).await;
+    
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 1, "method": "resources/list"})).await;
+    
+    let resources = response["result"]["resources"].as_array().unwrap();
+    let names: Vec<_> = resources.iter().map(|r| r["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["out.webp", "a b.dng"]);
+    assert_eq!(resources[0]["mimeType"], "image/webp");
+    assert_eq!(resources[1]["mimeType"], "image/x-adobe-dng");
+    assert!(resources[1]["uri"].as_str().unwrap().ends_with("/raw/a%20b.dng"));
+    
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 2, "method": "resources/templates/list"})).await;
+    let template = response["result"]["resourceTemplates"][0]["uriTemplate"].as_str().unwrap();
+    assert!(template.starts_with("file:///") && template.ends_with("/{path}"));
+}
+
+#[tokio::test]
+async fn recorded_outputs_under_a_root_are_listed_once() {
+    let root = tempfile::tempdir().unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    for path in [root.path().join("a.png"), root.path().join("b.webp"), outside.path().join("c.webp")] {
+        std::fs::write(path, b"image").unwrap();
+    }
+    let outputs = Arc::new(OutputLog::default());
+    let handler = ResourceHandler::new(&[root.path().to_path_buf()], outputs.clone());
+    outputs.record(&root.path().join("b.webp"));
+    outputs.record(&outside.path().join("c.webp"));
+    
+    let listed = handler.list(None).await.unwrap();
+    
+    let names: Vec<_> = listed["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["name"].as_str().unwrap())
+        .collect();
+    // Outputs come first, newest first, then the rest of the root
+    assert_eq!(names, ["c.webp", "b.webp", "a.png"]);
+}
+
+#[tokio::test]
+async fn reads_images_as_base64_blobs() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("out.webp"), b"RIFF....WEBP").unwrap();
+    let client = start_server(root.path()).await;
+    let listed = request(&client, json!({"jsonrpc": "2.0", "id": 1, "method": "resources/list"})).await;
+    let uri = listed["result"]["resources"][0]["uri"].clone();
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 2,
+        "method": "resources/read",
+        "params": {"uri": uri}
+    }))
+    .await;
+    
+    let contents = &response["result"]["contents"][0];
+    assert_eq!(contents["mimeType"], "image/webp");
+    let blob = base64::engine::general_purpose::STANDARD
+        .decode(contents["blob"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(blob, b"RIFF....WEBP");
+}
+
+#[tokio::test]
+async fn files_outside_roots_are_not_found() {
+    let root = tempfile::tempdir().unwrap();
+    let outside = tempfile::NamedTempFile::new().unwrap();
+    let client = start_server(root.path()).await;
+    let uri = url::Url::from_file_path(outside.path()).unwrap().to_string();
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "resources/read",
+        "params": {"uri": uri}
+    }))
+    .await;
+    
+    assert_eq!(response["error"]["code"], -32002);
+}
+
+#[tokio::test]
+async fn subscribers_are_told_about_changes() {
+    let root = tempfile::tempdir().unwrap();
+    let watched = root.path().join("out.webp");
+    std::fs::write(&watched, b"first").unwrap();
+    let client = start_server(root.path()).await;
+    let uri = url::Url::from_file_path(watched.canonicalize().unwrap()).unwrap().to_string();
+    
+    let response = request(&client, json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "resources/subscribe",
+        "params": {"uri": uri}
+    }))
+    .await;
+    assert_eq!(response["result"], json!({}));
+    
+    std::fs::write(&watched, b"second").unwrap();
+    std::fs::write(root.path().join("new.png"), b"png").unwrap();
+    
+    let mut methods = Vec::new();
+    while methods.len() < 2 {
+        let message = recv(&client).await;
+        if message["method"] == "notifications/resources/updated" {
+            assert_eq!(message["params"]["uri"], uri);
+        }
+        methods.push(message["method"].as_str().unwrap().to_string());
+    }
+    methods.sort();
+    assert_eq!(methods, ["notifications/resources/list_changed", "notifications/resources/updated"]);
+}
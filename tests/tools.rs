@@ -0,0 +1,115 @@
+//! Tool registry pagination and tool list change notifications
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use mcp_imagemagick::handlers::{RequestContext, Tool, ToolAnnotations, ToolRegistry};
+use mcp_imagemagick::transport::Transport;
+use mcp_imagemagick::ServerConfig;
+use serde_json::{json, Value};
+
+use common::{recv, request, write_script};
+
+struct Echo(&'static str);
+
+#[async_trait]
+impl Tool for Echo {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+    
+    fn title(&self) -> &'static str {
+        "Echo"
+    }
+    
+    fn description(&self) -> &'static str {
+        "Return the arguments"
+    }
+    
+    fn annotations(&self) -> ToolAnnotations {
+        ToolAnnotations::READ_ONLY
+    }
+    
+    fn input_schema(&self) -> Value {
+        json!({ "type": "object" })
+    }
+    
+    async fn call(&self, arguments: Value, _ctx: &RequestContext) -> mcp_imagemagick::Result<Value> {
+        Ok(json!({ "content": [{ "type": "text", "text": arguments.to_string() }] }))
+    }
+}
+
+fn tool_names(response: &Value) -> Vec<String> {
+    response["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn registry_pages_through_tools() {
+    let names: Vec<&'static str> = (0..120).map(|i| &*format!("echo_{}", i).leak()).collect();
+    let mut registry = ToolRegistry::new();
+    for name in &names {
+        registry.register(Arc::new(Echo(name)));
+    }
+    
+    let mut listed = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let page = registry.list(cursor.as_deref()).unwrap();
+        for tool in page["tools"].as_array().unwrap() {
+            listed.push(tool["name"].as_str().unwrap().to_string());
+        }
+        match page["nextCursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+    
+    assert_eq!(listed, names);
+    assert!(registry.list(Some("not a cursor")).is_err());
+}
+
+#[tokio::test]
+async fn converters_appearing_change_the_tool_list() {
+    // Only this test touches PATH: it decides which converters exist
+    let bin = tempfile::tempdir().unwrap();
+    std::env::set_var("PATH", bin.path());
+    
+    let config = ServerConfig {
+        converter_poll_interval: Duration::from_millis(50),
+        ..ServerConfig::default()
+    };
+    let (client, _server) = common::start_server(config);
+    
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}})).await;
+    assert_eq!(response["result"]["capabilities"]["tools"]["listChanged"], true);
+    client
+        .write_message(&json!({"jsonrpc": "2.0", "method": "notifications/initialized"}))
+        .await
+        .unwrap();
+    
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"})).await;
+    assert_eq!(tool_names(&response), ["check_converters"]);
+    
+    // Install darktable mid-session
+    let darktable = bin.path().join("darktable-cli");
+    write_script(&darktable, "#!/bin/sh\nexit 1\n");
+    
+    let notification = recv(&client).await;
+    assert_eq!(notification["method"], "notifications/tools/list_changed");
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 3, "method": "tools/list"})).await;
+    assert_eq!(tool_names(&response), ["convert_dng_to_webp", "convert_image", "process_pipeline", "batch_convert", "check_converters"]);
+    
+    std::fs::remove_file(&darktable).unwrap();
+    let notification = recv(&client).await;
+    assert_eq!(notification["method"], "notifications/tools/list_changed");
+    let response = request(&client, json!({"jsonrpc": "2.0", "id": 4, "method": "tools/list"})).await;
+    assert_eq!(tool_names(&response), ["check_converters"]);
+}